@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::io::{self, Cursor};
+
+use bytes::Bytes;
+use haste_core::demostream::{CmdHeader, ReadCmdError, ReadCmdHeaderError};
+
+use crate::demostream::read_cmd_header;
+use crate::streambuffer::{
+    PacketType, SeekableStreamBuffer, StreamBuffer, cursor_is_at_eof, cursor_read_cmd,
+    cursor_skip_cmd,
+};
+
+// NOTE: buffer layout is:
+// - signon (start fragment);
+// - full fragment of the oldest retained group;
+// - delta fragments of the oldest group, followed by fragments of newer groups.
+//
+// each group begins with a full fragment. full fragments of groups other than the oldest one are
+// not a part of the data (unless they came from the stream, for example after a resync), they are
+// kept on the side and spliced in once the group becomes the oldest one. this way reading data
+// from the beginning always produces valid state.
+
+/// options for [`crate::BroadcastHttp::start_streaming_and_buffer_bounded`].
+#[derive(Debug, Clone)]
+pub struct BoundedBufferOptions {
+    /// how many groups (a full fragment and deltas that follow it) to retain.
+    pub max_full_frames: usize,
+    /// how many delta fragments go into a group. an extra full fragment is requested each
+    /// `fragments_per_full_frame` fragments.
+    pub fragments_per_full_frame: usize,
+}
+
+impl Default for BoundedBufferOptions {
+    fn default() -> Self {
+        Self {
+            max_full_frames: 4,
+            // NOTE: with keyframe interval of 3 seconds that's a minute.
+            fragments_per_full_frame: 20,
+        }
+    }
+}
+
+struct Group {
+    full: Bytes,
+    /// whether full fragment is in the data (it is always in the data for the oldest group).
+    full_is_inline: bool,
+    /// length of group's region within the data.
+    len: usize,
+    num_deltas: usize,
+}
+
+/// retains signon data and last few full fragments with deltas that follow them (see
+/// [`BoundedBufferOptions`]).
+pub struct BoundedBuffer {
+    cursor: Cursor<Vec<u8>>,
+    signon_len: usize,
+    groups: VecDeque<Group>,
+    options: BoundedBufferOptions,
+}
+
+impl BoundedBuffer {
+    pub fn new(options: BoundedBufferOptions) -> Self {
+        Self {
+            cursor: Cursor::default(),
+            signon_len: 0,
+            groups: VecDeque::default(),
+            options,
+        }
+    }
+
+    // NOTE: appending must not move the read position.
+    fn append(&mut self, data: &[u8]) {
+        self.cursor.get_mut().extend_from_slice(data);
+    }
+
+    fn push_signon(&mut self, signon: &[u8]) {
+        self.append(signon);
+        self.signon_len = self.cursor.get_ref().len();
+    }
+
+    /// full fragment that came from the stream.
+    fn push_full(&mut self, full: Bytes) {
+        self.append(&full);
+        self.groups.push_back(Group {
+            len: full.len(),
+            full,
+            full_is_inline: true,
+            num_deltas: 0,
+        });
+        self.evict();
+    }
+
+    fn push_delta(&mut self, delta: &[u8]) {
+        self.append(delta);
+        match self.groups.back_mut() {
+            Some(group) => {
+                group.len += delta.len();
+                group.num_deltas += 1;
+            }
+            // NOTE: there's always a full fragment before deltas, but just in case.
+            None => self.signon_len = self.cursor.get_ref().len(),
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.groups.len() > self.options.max_full_frames.max(1) {
+            let Some(oldest) = self.groups.pop_front() else {
+                break;
+            };
+            let Some(next) = self.groups.front_mut() else {
+                break;
+            };
+
+            let start = self.signon_len;
+            let end = start + oldest.len;
+            let inserted: &[u8] = if next.full_is_inline {
+                &[]
+            } else {
+                next.full_is_inline = true;
+                next.len += next.full.len();
+                &next.full
+            };
+            self.cursor
+                .get_mut()
+                .splice(start..end, inserted.iter().copied());
+
+            let pos = self.cursor.position() as usize;
+            let pos = if pos < start {
+                pos
+            } else if pos < end {
+                start
+            } else {
+                pos - oldest.len + inserted.len()
+            };
+            self.cursor.set_position(pos as u64);
+        }
+    }
+}
+
+impl StreamBuffer for BoundedBuffer {
+    fn push(&mut self, packet_type: PacketType, packet: &Bytes) {
+        match packet_type {
+            PacketType::Start => self.push_signon(packet),
+            PacketType::Full => self.push_full(packet.clone()),
+            PacketType::Delta => self.push_delta(packet),
+        }
+    }
+
+    /// whether the next delta should start a new group.
+    fn needs_full(&self) -> bool {
+        self.groups
+            .back()
+            .is_some_and(|group| group.num_deltas >= self.options.fragments_per_full_frame)
+    }
+
+    /// full fragment that was requested to start a new group, it does not go into the data.
+    fn splice_full(&mut self, full: Bytes) {
+        self.groups.push_back(Group {
+            full,
+            full_is_inline: false,
+            len: 0,
+            num_deltas: 0,
+        });
+        self.evict();
+    }
+
+    #[inline]
+    fn is_at_eof(&self) -> bool {
+        cursor_is_at_eof(&self.cursor)
+    }
+
+    #[inline]
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
+        read_cmd_header(&mut self.cursor)
+    }
+
+    #[inline]
+    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
+        cursor_read_cmd(&mut self.cursor, cmd_header)
+    }
+
+    #[inline]
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        cursor_skip_cmd(&mut self.cursor, cmd_header)
+    }
+}
+
+impl SeekableStreamBuffer for BoundedBuffer {
+    #[inline]
+    fn cursor(&self) -> &Cursor<Vec<u8>> {
+        &self.cursor
+    }
+
+    #[inline]
+    fn cursor_mut(&mut self) -> &mut Cursor<Vec<u8>> {
+        &mut self.cursor
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_evict() {
+        let mut buffer = BoundedBuffer::new(BoundedBufferOptions {
+            max_full_frames: 2,
+            fragments_per_full_frame: 2,
+        });
+
+        buffer.push_signon(b"s");
+        buffer.push_full(Bytes::from_static(b"F0"));
+        assert!(!buffer.needs_full());
+        buffer.push_delta(b"d0");
+        buffer.push_delta(b"d1");
+        assert!(buffer.needs_full());
+        buffer.splice_full(Bytes::from_static(b"F2"));
+        buffer.push_delta(b"d2");
+        buffer.push_delta(b"d3");
+        assert_eq!(buffer.cursor.get_ref(), b"sF0d0d1d2d3");
+
+        // reader is at d3
+        buffer.cursor.set_position(9);
+        buffer.splice_full(Bytes::from_static(b"F4"));
+        buffer.push_delta(b"d4");
+        assert_eq!(buffer.cursor.get_ref(), b"sF2d2d3d4");
+        assert_eq!(buffer.cursor.position(), 5);
+
+        // reader is within the group that is about to be dropped
+        buffer.cursor.set_position(3);
+        buffer.push_delta(b"d5");
+        buffer.push_full(Bytes::from_static(b"F6"));
+        assert_eq!(buffer.cursor.get_ref(), b"sF4d4d5F6");
+        assert_eq!(buffer.cursor.position(), 1);
+    }
+}
@@ -0,0 +1,471 @@
+use std::io::{Seek, Write};
+
+use haste_core::democlip::for_each_packet_msg;
+use haste_core::demostream::{CmdHeader, DemoStream};
+use haste_core::demowriter::DemoWriter;
+use haste_core::stringtables::StringTableContainer;
+use prost::Message;
+use valveprotos::common::{
+    CDemoFileHeader, CDemoFileInfo, CDemoFullPacket, CsvcMsgCreateStringTable,
+    CsvcMsgPacketEntities, CsvcMsgServerInfo, CsvcMsgUpdateStringTable, EDemoCommands, SvcMessages,
+};
+
+// NOTE: broadcasts differ from demo files in:
+// - cmd header encoding (see demostream.rs);
+// - send tables and packet bodies are not protobuf-encoded;
+// - there's no file header, no sync tick, no stop and no file info;
+// - full frames (`/full` fragments) are regular packets.
+//
+// converter fills in the gaps. full frames are recognized by non-delta packet entities and are
+// written out twice: as a packet (that's what parsing of the broadcast would do) and as a full
+// packet (parser needs those to be able to seek). full packets also carry a snapshot of string
+// tables; converter keeps track of string tables on its own to be able to provide one.
+
+/// converts broadcasts (from [`crate::BroadcastFile`] or [`crate::BroadcastHttp`]) into demo
+/// files that can be read with [`haste_core::demofile::DemoFile`] (or opened by anything that can
+/// open regular replays).
+pub struct BroadcastConverter<W: Write + Seek> {
+    demo_writer: DemoWriter<W>,
+    did_write_sync_tick: bool,
+    last_tick: i32,
+    tick_interval: Option<f32>,
+    string_tables: StringTableContainer,
+}
+
+impl<W: Write + Seek> BroadcastConverter<W> {
+    /// same as [`BroadcastConverter::start_writing_with_file_header`], but the file header
+    /// contains nothing but the demo file stamp.
+    pub fn start_writing(wtr: W) -> Result<Self, anyhow::Error> {
+        Self::start_writing_with_file_header(wtr, &CDemoFileHeader::default())
+    }
+
+    /// creates a new [`BroadcastConverter`] that writes into the given writer. provided file
+    /// header is written as the first cmd (broadcasts do not have it); demo file stamp is
+    /// filled in if empty.
+    ///
+    /// # performance note
+    ///
+    /// for optimal performance make sure to provide a writer that implements buffering (for
+    /// example [`std::io::BufWriter`]).
+    pub fn start_writing_with_file_header(
+        wtr: W,
+        file_header: &CDemoFileHeader,
+    ) -> Result<Self, anyhow::Error> {
+        let mut file_header = file_header.clone();
+        if file_header.demo_file_stamp.is_empty() {
+            file_header.demo_file_stamp = "PBDEMS2\0".to_string();
+        }
+
+        let mut demo_writer = DemoWriter::start_writing(wtr)?;
+        demo_writer.write_cmd(
+            EDemoCommands::DemFileHeader,
+            -1,
+            &file_header.encode_to_vec(),
+            false,
+        )?;
+
+        Ok(Self {
+            demo_writer,
+            did_write_sync_tick: false,
+            last_tick: -1,
+            tick_interval: None,
+            string_tables: StringTableContainer::default(),
+        })
+    }
+
+    /// converts and writes a cmd that was read from `S`.
+    pub fn write_cmd<S: DemoStream>(
+        &mut self,
+        cmd_header: &CmdHeader,
+        body: &[u8],
+    ) -> Result<(), anyhow::Error> {
+        match cmd_header.cmd {
+            // NOTE: those are written by the converter itself.
+            EDemoCommands::DemFileHeader | EDemoCommands::DemStop | EDemoCommands::DemFileInfo => {
+                return Ok(());
+            }
+            // NOTE: broadcasts don't seem to contain full packets; full frames come in as regular
+            // packets and are handled below.
+            EDemoCommands::DemFullPacket => return Ok(()),
+            EDemoCommands::DemSyncTick => self.did_write_sync_tick = true,
+            // NOTE: DemSyncTick separates signon from the rest; parser relies on it to be able to
+            // seek.
+            EDemoCommands::DemPacket if !self.did_write_sync_tick => {
+                self.demo_writer
+                    .write_cmd(EDemoCommands::DemSyncTick, -1, &[], false)?;
+                self.did_write_sync_tick = true;
+            }
+            _ => {}
+        }
+
+        let mut full_packet = None;
+        let body = match cmd_header.cmd {
+            EDemoCommands::DemSendTables => S::decode_cmd_send_tables(body)?.encode_to_vec(),
+            EDemoCommands::DemClassInfo => S::decode_cmd_class_info(body)?.encode_to_vec(),
+            EDemoCommands::DemPacket | EDemoCommands::DemSignonPacket => {
+                let packet = S::decode_cmd_packet(body)?;
+                if cmd_header.cmd == EDemoCommands::DemSignonPacket && self.tick_interval.is_none()
+                {
+                    self.tick_interval = find_tick_interval(packet.data())?;
+                }
+                update_string_tables(&mut self.string_tables, packet.data())?;
+                if cmd_header.cmd == EDemoCommands::DemPacket && is_full_frame(packet.data())? {
+                    full_packet = Some(CDemoFullPacket {
+                        string_table: Some(self.string_tables.to_snapshot()),
+                        packet: Some(packet.clone()),
+                    });
+                }
+                packet.encode_to_vec()
+            }
+            _ => body.to_vec(),
+        };
+
+        self.demo_writer
+            .write_cmd(cmd_header.cmd, cmd_header.tick, &body, !body.is_empty())?;
+        if let Some(full_packet) = full_packet {
+            self.demo_writer.write_cmd(
+                EDemoCommands::DemFullPacket,
+                cmd_header.tick,
+                &full_packet.encode_to_vec(),
+                true,
+            )?;
+        }
+        self.last_tick = self.last_tick.max(cmd_header.tick);
+
+        Ok(())
+    }
+
+    /// converts and writes all cmds that are available in the stream.
+    ///
+    /// for [`crate::BroadcastHttp`] this needs to be called after each successful `next_packet`.
+    pub fn write_stream<S: DemoStream>(
+        &mut self,
+        demo_stream: &mut S,
+    ) -> Result<(), anyhow::Error> {
+        loop {
+            match demo_stream.read_cmd_header() {
+                Ok(cmd_header) => {
+                    let body = demo_stream.read_cmd(&cmd_header)?;
+                    self.write_cmd::<S>(&cmd_header, body)?;
+                }
+                Err(_) if demo_stream.is_at_eof().unwrap_or_default() => return Ok(()),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// writes stop and file info cmds. returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, anyhow::Error> {
+        self.demo_writer
+            .write_cmd(EDemoCommands::DemStop, self.last_tick, &[], false)?;
+
+        let file_info = CDemoFileInfo {
+            playback_ticks: Some(self.last_tick),
+            playback_time: self
+                .tick_interval
+                .map(|tick_interval| self.last_tick as f32 * tick_interval),
+            ..Default::default()
+        };
+        self.demo_writer
+            .write_file_info(self.last_tick, &file_info)?;
+
+        self.demo_writer.finish().map_err(Into::into)
+    }
+}
+
+fn is_full_frame(data: &[u8]) -> Result<bool, anyhow::Error> {
+    let mut is_full_frame = false;
+    for_each_packet_msg(data, |msg_type, data| {
+        if msg_type == SvcMessages::SvcPacketEntities as u32 {
+            is_full_frame |= !CsvcMsgPacketEntities::decode(data)?.is_delta();
+        }
+        Ok(())
+    })?;
+    Ok(is_full_frame)
+}
+
+fn update_string_tables(
+    string_tables: &mut StringTableContainer,
+    data: &[u8],
+) -> Result<(), anyhow::Error> {
+    for_each_packet_msg(data, |msg_type, data| {
+        if msg_type == SvcMessages::SvcCreateStringTable as u32 {
+            string_tables.handle_create_msg(&CsvcMsgCreateStringTable::decode(data)?)?;
+        } else if msg_type == SvcMessages::SvcUpdateStringTable as u32 {
+            string_tables.handle_update_msg(&CsvcMsgUpdateStringTable::decode(data)?)?;
+        }
+        Ok(())
+    })
+}
+
+fn find_tick_interval(data: &[u8]) -> Result<Option<f32>, anyhow::Error> {
+    let mut tick_interval = None;
+    for_each_packet_msg(data, |msg_type, data| {
+        if msg_type == SvcMessages::SvcServerInfo as u32 {
+            tick_interval = CsvcMsgServerInfo::decode(data)?.tick_interval;
+        }
+        Ok(())
+    })?;
+    Ok(tick_interval)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use haste_core::democlip::PacketBuilder;
+    use haste_core::demofile::DemoFile;
+    use haste_core::fieldvalue::FieldValue;
+    use haste_core::parser::Parser;
+    use haste_core::testdemo::{
+        HEALTH, ROUND, TEST_ENTITY_CLASS_ID, TEST_GAME_RULES_CLASS_ID, TestDemoBuilder,
+        entities_state, string_tables_state,
+    };
+    use valveprotos::common::{CDemoPacket, CDemoSendTables};
+
+    use super::*;
+    use crate::BroadcastFile;
+    use crate::broadcastserver::{BroadcastServer, BroadcastServerOptions};
+
+    fn write_broadcast_cmd(buf: &mut Vec<u8>, cmd: EDemoCommands, tick: i32, body: &[u8]) {
+        buf.push(cmd as u8);
+        buf.extend_from_slice(&(tick as u32).to_le_bytes());
+        buf.push(0);
+        buf.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        buf.extend_from_slice(body);
+    }
+
+    #[test]
+    fn test_convert() -> Result<(), anyhow::Error> {
+        let mut packet_builder = PacketBuilder::new();
+        let server_info = CsvcMsgServerInfo {
+            tick_interval: Some(1.0 / 30.0),
+            ..Default::default()
+        };
+        packet_builder.push_msg(
+            SvcMessages::SvcServerInfo as u32,
+            &server_info.encode_to_vec(),
+        );
+        let signon_packet_data = packet_builder.finish().data.unwrap_or_default();
+        let send_tables_data = b"send tables".to_vec();
+        let packet_entities_data = |is_delta: bool| {
+            let mut packet_builder = PacketBuilder::new();
+            let packet_entities = CsvcMsgPacketEntities {
+                is_delta: Some(is_delta),
+                ..Default::default()
+            };
+            packet_builder.push_msg(
+                SvcMessages::SvcPacketEntities as u32,
+                &packet_entities.encode_to_vec(),
+            );
+            packet_builder.finish().data.unwrap_or_default()
+        };
+        let full_frame_data = packet_entities_data(false);
+        let packet_data = packet_entities_data(true);
+
+        let mut broadcast = Vec::new();
+        write_broadcast_cmd(
+            &mut broadcast,
+            EDemoCommands::DemSignonPacket,
+            0,
+            &signon_packet_data,
+        );
+        write_broadcast_cmd(
+            &mut broadcast,
+            EDemoCommands::DemSendTables,
+            0,
+            &[[0u8; 4].as_slice(), &send_tables_data].concat(),
+        );
+        write_broadcast_cmd(
+            &mut broadcast,
+            EDemoCommands::DemPacket,
+            30,
+            &full_frame_data,
+        );
+        write_broadcast_cmd(&mut broadcast, EDemoCommands::DemPacket, 60, &packet_data);
+
+        let mut broadcast_file = BroadcastFile::start_reading(Cursor::new(broadcast));
+        let mut converter = BroadcastConverter::start_writing(Cursor::new(Vec::new()))?;
+        converter.write_stream(&mut broadcast_file)?;
+        let data = converter.finish()?.into_inner();
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut next_cmd = || {
+            let cmd_header = demo_file.read_cmd_header()?;
+            let body = demo_file.read_cmd(&cmd_header)?.to_vec();
+            Ok::<_, anyhow::Error>((cmd_header.cmd, cmd_header.tick, body))
+        };
+
+        assert_eq!(next_cmd()?.0, EDemoCommands::DemFileHeader);
+        let (cmd, _, body) = next_cmd()?;
+        assert_eq!(cmd, EDemoCommands::DemSignonPacket);
+        assert_eq!(
+            CDemoPacket::decode(body.as_slice())?.data(),
+            signon_packet_data
+        );
+        let (cmd, _, body) = next_cmd()?;
+        assert_eq!(cmd, EDemoCommands::DemSendTables);
+        assert_eq!(
+            CDemoSendTables::decode(body.as_slice())?.data(),
+            send_tables_data
+        );
+        assert_eq!(next_cmd()?.0, EDemoCommands::DemSyncTick);
+        // full frame is written both as packet and as full packet
+        let (cmd, tick, body) = next_cmd()?;
+        assert_eq!((cmd, tick), (EDemoCommands::DemPacket, 30));
+        assert_eq!(
+            CDemoPacket::decode(body.as_slice())?.data(),
+            full_frame_data
+        );
+        let (cmd, tick, body) = next_cmd()?;
+        assert_eq!((cmd, tick), (EDemoCommands::DemFullPacket, 30));
+        let full_packet = CDemoFullPacket::decode(body.as_slice())?;
+        // there are no string tables in the broadcast, but the snapshot must be there
+        assert_eq!(full_packet.string_table, Some(Default::default()));
+        assert_eq!(
+            full_packet.packet.map(|packet| packet.data().to_vec()),
+            Some(full_frame_data)
+        );
+        let (cmd, tick, body) = next_cmd()?;
+        assert_eq!((cmd, tick), (EDemoCommands::DemPacket, 60));
+        assert_eq!(CDemoPacket::decode(body.as_slice())?.data(), packet_data);
+        assert_eq!(next_cmd()?.0, EDemoCommands::DemStop);
+
+        let file_info = demo_file.file_info()?;
+        assert_eq!(file_info.playback_ticks(), 60);
+        assert!((file_info.playback_time() - 2.0).abs() < f32::EPSILON);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_to_tick() -> Result<(), anyhow::Error> {
+        // instancebaseline changes within regular packets, entities that are created later rely
+        // on it.
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(100))])
+            .create_entity(
+                1,
+                TEST_GAME_RULES_CLASS_ID,
+                1,
+                &[(ROUND, FieldValue::U64(1))],
+            )
+            .create_entity(2, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(10)?
+            .write_full_packet(10)?;
+        for tick in (40..2400).step_by(30) {
+            builder.update_entity(2, &[(HEALTH, FieldValue::I64(tick as i64))]);
+            match tick {
+                1000 => {
+                    builder.update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(50))]);
+                }
+                2110 => {
+                    builder.create_entity(3, TEST_ENTITY_CLASS_ID, 1, &[]);
+                }
+                2200 => {
+                    builder
+                        .update_entity(1, &[(ROUND, FieldValue::U64(2))])
+                        .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(70))])
+                        .create_entity(4, TEST_ENTITY_CLASS_ID, 1, &[]);
+                }
+                _ => {}
+            }
+            builder.write_packet(tick)?;
+        }
+        let data = builder.finish(2400)?;
+
+        let demo_file = DemoFile::start_reading(Cursor::new(&data))?;
+        let options = BroadcastServerOptions {
+            keyframe_interval: 2,
+            speed: f64::INFINITY,
+        };
+        let mut server = BroadcastServer::new(demo_file, options)?;
+
+        // fragments start at ticks 10, 70, 130, ..; each fragment contains 2 packets. there's a
+        // full frame every 10 fragments (as if the broadcast was re-joined), the converted demo
+        // will thus have a few full packets to seek to.
+        let mut responses = vec![server.respond("/tv/42/0/start")];
+        for fragment in 1..server.num_fragments() {
+            if fragment % 10 == 1 {
+                responses.push(server.respond(&format!("/tv/42/{fragment}/full")));
+            }
+            responses.push(server.respond(&format!("/tv/42/{fragment}/delta")));
+        }
+        assert!(responses.iter().all(|response| response.status == 200));
+        // broadcast that ends with the given fragment
+        let broadcast = |fragment: usize| {
+            let num_responses = fragment + 1 + fragment.div_ceil(10);
+            let bodies: Vec<&[u8]> = responses[..num_responses]
+                .iter()
+                .map(|response| response.body.as_slice())
+                .collect();
+            BroadcastFile::start_reading(Cursor::new(bodies.concat()))
+        };
+
+        let mut converter = BroadcastConverter::start_writing(Cursor::new(Vec::new()))?;
+        converter.write_stream(&mut broadcast(server.num_fragments() - 1))?;
+        let converted = converter.finish()?.into_inner();
+
+        // full packets must carry instancebaseline as of their tick (it changes at 1000).
+        let mut demo_file = DemoFile::start_reading(Cursor::new(&converted))?;
+        let mut full_packet_baselines = Vec::new();
+        while let Ok(cmd_header) = demo_file.read_cmd_header() {
+            if cmd_header.cmd != EDemoCommands::DemFullPacket {
+                demo_file.skip_cmd(&cmd_header)?;
+                continue;
+            }
+            let full_packet = DemoFile::<Cursor<&Vec<u8>>>::decode_cmd_full_packet(
+                demo_file.read_cmd(&cmd_header)?,
+            )?;
+            let baseline = full_packet
+                .string_table
+                .iter()
+                .flat_map(|string_table| &string_table.tables)
+                .filter(|table| table.table_name() == "instancebaseline")
+                .flat_map(|table| &table.items)
+                .find(|item| item.str() == TEST_ENTITY_CLASS_ID.to_string())
+                .map(|item| item.data().to_vec());
+            full_packet_baselines.push((cmd_header.tick, baseline));
+        }
+        let ticks: Vec<i32> = full_packet_baselines
+            .iter()
+            .map(|(tick, _)| *tick)
+            .collect();
+        assert_eq!(ticks, [70, 670, 1270, 1870]);
+        let baselines: Vec<_> = full_packet_baselines
+            .into_iter()
+            .map(|(_, baseline)| baseline)
+            .collect();
+        assert!(baselines[0].is_some());
+        assert_eq!(baselines[0], baselines[1]);
+        assert_ne!(baselines[1], baselines[2]);
+        assert_eq!(baselines[2], baselines[3]);
+
+        let mut parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&converted))?)?;
+
+        // NOTE: seeking backwards after instancebaseline changes must not leave anything behind.
+        for fragment in [37, 6, 22, 35] {
+            let tick = 40 + fragment as i32 * 60;
+
+            let mut broadcast_parser = Parser::from_stream(broadcast(fragment))?;
+            broadcast_parser.run_to_end()?;
+            assert_eq!(broadcast_parser.context().tick(), tick);
+
+            parser.run_to_tick(tick)?;
+            assert_eq!(parser.context().tick(), tick);
+            assert_eq!(
+                entities_state(parser.context()),
+                entities_state(broadcast_parser.context()),
+                "{tick}"
+            );
+            assert_eq!(
+                string_tables_state(parser.context()),
+                string_tables_state(broadcast_parser.context()),
+                "{tick}"
+            );
+        }
+
+        Ok(())
+    }
+}
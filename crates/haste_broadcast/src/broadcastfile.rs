@@ -2,13 +2,14 @@ use std::io::{self, Read, Seek, SeekFrom};
 
 use haste_core::demofile::DEMO_RECORD_BUFFER_SIZE;
 use haste_core::demostream::{
-    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError,
+    scan_for_last_tick, CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError,
+    SeekableDemoStream,
 };
 use valveprotos::common::{CDemoClassInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables};
 
 use crate::demostream::{
     decode_cmd_class_info, decode_cmd_full_packet, decode_cmd_packet, decode_cmd_send_tables,
-    read_cmd_header, scan_for_last_tick,
+    read_cmd_header,
 };
 
 /// allows to read recorded broadcasts.
@@ -45,21 +46,14 @@ impl<R: Read + Seek> DemoStream for BroadcastFile<R> {
     // stream ops
     // ----
 
-    /// delegated from [`std::io::Seek`].
     #[inline]
-    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
-        self.rdr.seek(pos)
+    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
+        Ok(self.stream_position()? == self.stream_len()?)
     }
 
-    /// delegated from [`std::io::Seek`].
-    ///
-    /// # note
-    ///
-    /// be aware that this method can be quite expensive. it might be best to make sure not to call
-    /// it too frequently.
     #[inline]
-    fn stream_position(&mut self) -> Result<u64, io::Error> {
-        self.rdr.stream_position()
+    fn is_at_or_past_eof(&mut self) -> Result<bool, io::Error> {
+        Ok(self.stream_position()? >= self.stream_len()?)
     }
 
     // cmd header
@@ -100,8 +94,30 @@ impl<R: Read + Seek> DemoStream for BroadcastFile<R> {
         decode_cmd_full_packet(data)
     }
 
-    // other
-    // ----
+    #[inline]
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.seek(SeekFrom::Current(cmd_header.body_size as i64))
+            .map(|_| ())
+    }
+}
+
+impl<R: Read + Seek> SeekableDemoStream for BroadcastFile<R> {
+    /// delegated from [`std::io::Seek`].
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
+        self.rdr.seek(pos)
+    }
+
+    /// delegated from [`std::io::Seek`].
+    ///
+    /// # note
+    ///
+    /// be aware that this method can be quite expensive. it might be best to make sure not to call
+    /// it too frequently.
+    #[inline]
+    fn stream_position(&mut self) -> Result<u64, io::Error> {
+        self.rdr.stream_position()
+    }
 
     fn start_position(&self) -> u64 {
         0
@@ -1,21 +1,27 @@
+use std::collections::hash_map::RandomState;
 use std::error::Error;
-use std::io::{self, BufRead, Cursor, Seek, SeekFrom, Write};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Seek, SeekFrom};
 use std::marker::PhantomData;
 use std::time::{Duration, Instant};
 
-use bytes::buf::Reader;
-use bytes::{Buf, Bytes};
+use bytes::Bytes;
 use haste_core::demostream::{
-    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError,
+    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError, SeekableDemoStream,
+    scan_for_last_tick,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use valveprotos::common::{CDemoClassInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables};
 
+use crate::boundedbuffer::{BoundedBuffer, BoundedBufferOptions};
 use crate::demostream::{
     decode_cmd_class_info, decode_cmd_full_packet, decode_cmd_packet, decode_cmd_send_tables,
-    read_cmd_header, scan_for_last_tick,
 };
 use crate::httpclient::HttpClient;
+use crate::sleep::{DefaultSleep, Sleep, ThreadSleep};
+use crate::streambuffer::{
+    LastPacketBuffer, PacketType, SeekableBuffer, SeekableStreamBuffer, StreamBuffer,
+};
 
 // thanks to Bulbasaur (/ johnpyp) for bringing up tv broadcasts in discord, see
 // https://discord.com/channels/1275127765879754874/1276578605836668969/1289323757403504734; and
@@ -45,6 +51,7 @@ use crate::httpclient::HttpClient;
 // -> STREAM_DELTAFRAMES
 
 const MAX_DELTAFRAME_RETRIES: u32 = 5;
+const MAX_RESYNCS: u32 = 3;
 
 // from wiresharking deadlock:
 //   GET /tv/18895867/sync HTTP/1.1\r\n
@@ -87,7 +94,7 @@ pub fn default_headers(app_id: u32) -> Result<http::HeaderMap, http::header::Inv
 // ----
 // broadcast http client
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SyncResponse {
     /// start tick of the current fragment
     pub tick: i32,
@@ -126,19 +133,37 @@ pub enum BroadcastHttpClientError<HttpClientError: Error + Send + Sync + 'static
     StatusCode(http::StatusCode),
     #[error("could not deserialize json")]
     JsonError(#[source] serde_json::Error),
+    #[error("request timed out")]
+    Timeout,
+}
+
+impl<HttpClientError: Error + Send + Sync + 'static> BroadcastHttpClientError<HttpClientError> {
+    /// errors that are likely to go away if the stream is re-synced (server errors, timeouts and
+    /// errors of the http client itself, e.g. connection resets).
+    fn is_transient(&self) -> bool {
+        match self {
+            Self::StatusCode(status_code) => status_code.is_server_error(),
+            Self::Timeout | Self::HttpClientError(_) => true,
+            _ => false,
+        }
+    }
 }
 
-struct BroadcasHttpClient<'client, C: HttpClient + 'client> {
+struct BroadcasHttpClient<'client, C: HttpClient + 'client, S: Sleep> {
     http_client: C,
     base_url: String,
+    request_timeout: Option<Duration>,
+    sleep: S,
     _marker: PhantomData<&'client ()>,
 }
 
-impl<'client, C: HttpClient + 'client> BroadcasHttpClient<'client, C> {
-    fn new(http_client: C, base_url: impl Into<String>) -> Self {
+impl<'client, C: HttpClient + 'client, S: Sleep> BroadcasHttpClient<'client, C, S> {
+    fn new(http_client: C, base_url: impl Into<String>, sleep: S) -> Self {
         Self {
             http_client,
             base_url: base_url.into(),
+            request_timeout: None,
+            sleep,
             _marker: PhantomData,
         }
     }
@@ -152,7 +177,14 @@ impl<'client, C: HttpClient + 'client> BroadcasHttpClient<'client, C> {
             .uri(url)
             .body(Bytes::default())
             .map_err(BroadcastHttpClientError::BuildRequestError)?;
-        let response = self.http_client.execute(request).await?;
+        let response = match self.request_timeout {
+            Some(timeout) => self
+                .sleep
+                .timeout(timeout, self.http_client.execute(request))
+                .await
+                .ok_or(BroadcastHttpClientError::Timeout)??,
+            None => self.http_client.execute(request).await?,
+        };
         if response.status().is_client_error() || response.status().is_server_error() {
             Err(BroadcastHttpClientError::StatusCode(response.status()))
         } else {
@@ -196,6 +228,95 @@ impl<'client, C: HttpClient + 'client> BroadcasHttpClient<'client, C> {
     }
 }
 
+// ----
+// retry policy
+
+/// controls how [`BroadcastHttp`] deals with fragments that are not available yet and with
+/// failures.
+///
+/// default policy mimics the game: fragments that are not available (404) are re-requested each
+/// `keyframe_interval` up to 5 times, after that the stream is considered to be ended.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// how many times a delta fragment that responds with 404 is re-requested before the stream
+    /// is considered to be ended.
+    pub max_retries: u32,
+    /// delay before the first retry. `None` means `keyframe_interval` from the sync response.
+    pub initial_backoff: Option<Duration>,
+    /// each subsequent retry waits `backoff_multiplier` times longer than the previous one.
+    pub backoff_multiplier: f64,
+    pub max_backoff: Duration,
+    /// randomizes delays by up to +- `jitter` of the delay; 0.0..=1.0.
+    pub jitter: f64,
+    /// time limit for a single request; timed out requests result in
+    /// [`BroadcastHttpClientError::Timeout`].
+    ///
+    /// NOTE: enforced by [`Sleep::timeout`], which [`ThreadSleep`] does not implement.
+    pub request_timeout: Option<Duration>,
+    /// stream is re-synced (sync is re-fetched and followed by the next full fragment) when it
+    /// falls behind the live fragment by more than this many fragments. `None` disables lag
+    /// checks.
+    ///
+    /// NOTE: this is not applied before the stream catches up with the live fragment for the
+    /// first time, thus it does not affect streams that were started from the past.
+    pub max_lag_fragments: Option<u32>,
+    /// how many times in a row the stream can be re-synced (after 5xx, timeouts, http client errors
+    /// or lags) before giving up.
+    pub max_resyncs: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: MAX_DELTAFRAME_RETRIES,
+            initial_backoff: None,
+            backoff_multiplier: 1.0,
+            max_backoff: Duration::from_secs(60),
+            jitter: 0.0,
+            request_timeout: None,
+            max_lag_fragments: Some(5),
+            max_resyncs: MAX_RESYNCS,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, num_retries: u32, keyframe_interval: Duration, rng: &mut Rng) -> Duration {
+        // NOTE: math is done in f64 seconds; Duration::mul_f64 panics on overflow (and multiplier
+        // raised to the power of num_retries overflows easily). exponent is clamped because
+        // `num_retries as i32` would wrap into negatives past i32::MAX.
+        let max_backoff = self.max_backoff.as_secs_f64();
+        let initial_backoff = self.initial_backoff.unwrap_or(keyframe_interval);
+        let exp = num_retries.min(i32::MAX as u32) as i32;
+        let mut backoff = (initial_backoff.as_secs_f64()
+            * self.backoff_multiplier.max(0.0).powi(exp))
+        .min(max_backoff);
+        if self.jitter > 0.0 {
+            let jitter = self.jitter.min(1.0) * (rng.next_f64() * 2.0 - 1.0);
+            backoff = (backoff * (1.0 + jitter)).min(max_backoff);
+        }
+        Duration::try_from_secs_f64(backoff).unwrap_or(self.max_backoff)
+    }
+}
+
+// NOTE: jitter does not need good randomness; xorshift seeded from std's RandomState is more than
+// enough and allows to avoid depending on rand.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Self(RandomState::new().build_hasher().finish() | 1)
+    }
+
+    /// returns a number within 0.0..1.0.
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
 // ----
 // broadcast http
 
@@ -212,20 +333,31 @@ enum StreamState {
     },
 }
 
-enum StreamBuffer {
-    Last(Option<Reader<Bytes>>),
-    Seekable(Cursor<Vec<u8>>),
-}
-
-pub struct BroadcastHttp<'client, C: HttpClient + 'client> {
-    client: BroadcasHttpClient<'client, C>,
+/// [`DemoStream`] that is being received over http.
+///
+/// buffer (see [`StreamBuffer`]) decides what is being retained; [`SeekableDemoStream`] (and thus
+/// [`haste_core::parser::Parser::run_to_tick`]) is available only with buffers that retain data
+/// (see [`BroadcastHttp::start_streaming_and_buffer`]).
+pub struct BroadcastHttp<
+    'client,
+    C: HttpClient + 'client,
+    S: Sleep = DefaultSleep,
+    B: StreamBuffer = LastPacketBuffer,
+> {
+    client: BroadcasHttpClient<'client, C, S>,
     stream_fragment: i32,
     keyframe_interval: Duration,
     signup_fragment: i32,
     sync_response: SyncResponse,
+    sync_instant: Instant,
     stream_state: StreamState,
-    stream_buffer: StreamBuffer,
+    stream_buffer: B,
     total_ticks: Option<i32>,
+    retry_policy: RetryPolicy,
+    num_resyncs: u32,
+    /// whether the stream caught up with the live fragment at least once.
+    reached_live: bool,
+    rng: Rng,
 }
 
 impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
@@ -233,7 +365,7 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
         http_client: C,
         base_url: impl Into<String>,
     ) -> Result<Self, BroadcastHttpClientError<C::Error>> {
-        let client = BroadcasHttpClient::new(http_client, base_url);
+        let client = BroadcasHttpClient::new(http_client, base_url, DefaultSleep::default());
 
         let sync_response = client.get_sync().await?;
 
@@ -243,27 +375,190 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
             keyframe_interval: Duration::from_secs(sync_response.keyframe_interval as u64),
             signup_fragment: sync_response.signup_fragment,
             sync_response,
+            sync_instant: Instant::now(),
             stream_state: StreamState::Start,
-            stream_buffer: StreamBuffer::Last(None),
+            stream_buffer: LastPacketBuffer::default(),
             total_ticks: None,
+            retry_policy: RetryPolicy::default(),
+            num_resyncs: 0,
+            reached_live: false,
+            rng: Rng::new(),
         })
     }
 
-    /// buffer all packets. this enables seeking on [`DemoStream`].
-    pub async fn start_streaming_and_buffer(
+    /// starts streaming from the given fragment instead of the live one. fragments that are
+    /// already available are fetched back to back, without waiting for real-time.
+    ///
+    /// fragments that precede signup fragment do not exist, thus `fragment` is clamped to it.
+    pub async fn start_streaming_from_fragment(
         http_client: C,
         base_url: impl Into<String>,
+        fragment: i32,
     ) -> Result<Self, BroadcastHttpClientError<C::Error>> {
         let mut this = Self::start_streaming(http_client, base_url).await?;
-        this.stream_buffer = StreamBuffer::Seekable(Cursor::default());
+        this.stream_fragment = fragment.max(this.signup_fragment);
         Ok(this)
     }
 
+    /// starts streaming from the fragment that contains the given tick (see
+    /// [`BroadcastHttp::fragment_for_tick`]).
+    pub async fn start_streaming_from_tick(
+        http_client: C,
+        base_url: impl Into<String>,
+        tick: i32,
+    ) -> Result<Self, BroadcastHttpClientError<C::Error>> {
+        let mut this = Self::start_streaming(http_client, base_url).await?;
+        this.stream_fragment = this.fragment_for_tick(tick);
+        Ok(this)
+    }
+
+    /// starts streaming from the signup fragment, the very first one.
+    pub async fn start_streaming_from_beginning(
+        http_client: C,
+        base_url: impl Into<String>,
+    ) -> Result<Self, BroadcastHttpClientError<C::Error>> {
+        let mut this = Self::start_streaming(http_client, base_url).await?;
+        this.stream_fragment = this.signup_fragment;
+        Ok(this)
+    }
+
+    /// buffer all packets. this enables [`SeekableDemoStream`].
+    pub async fn start_streaming_and_buffer(
+        http_client: C,
+        base_url: impl Into<String>,
+    ) -> Result<
+        BroadcastHttp<'client, C, DefaultSleep, SeekableBuffer>,
+        BroadcastHttpClientError<C::Error>,
+    > {
+        let this = Self::start_streaming(http_client, base_url).await?;
+        Ok(this.with_buffer(SeekableBuffer::default()))
+    }
+
+    /// same as [`BroadcastHttp::start_streaming_and_buffer`], but retains only signon data and
+    /// last few full fragments with deltas that follow them (see [`BoundedBufferOptions`]).
+    /// extra full fragments are being requested to make that possible.
+    ///
+    /// seeking (/ [`haste_core::parser::Parser::run_to_tick`]) is possible only within the
+    /// retained window.
+    pub async fn start_streaming_and_buffer_bounded(
+        http_client: C,
+        base_url: impl Into<String>,
+        options: BoundedBufferOptions,
+    ) -> Result<
+        BroadcastHttp<'client, C, DefaultSleep, BoundedBuffer>,
+        BroadcastHttpClientError<C::Error>,
+    > {
+        let this = Self::start_streaming(http_client, base_url).await?;
+        Ok(this.with_buffer(BoundedBuffer::new(options)))
+    }
+}
+
+impl<'client, C: HttpClient + 'client, S: Sleep, B: StreamBuffer> BroadcastHttp<'client, C, S, B> {
+    /// replaces the [`Sleep`] implementation (which defaults to [`DefaultSleep`]).
+    pub fn with_sleep<S2: Sleep>(self, sleep: S2) -> BroadcastHttp<'client, C, S2, B> {
+        BroadcastHttp {
+            client: BroadcasHttpClient {
+                http_client: self.client.http_client,
+                base_url: self.client.base_url,
+                request_timeout: self.client.request_timeout,
+                sleep,
+                _marker: PhantomData,
+            },
+            stream_fragment: self.stream_fragment,
+            keyframe_interval: self.keyframe_interval,
+            signup_fragment: self.signup_fragment,
+            sync_response: self.sync_response,
+            sync_instant: self.sync_instant,
+            stream_state: self.stream_state,
+            stream_buffer: self.stream_buffer,
+            total_ticks: self.total_ticks,
+            retry_policy: self.retry_policy,
+            num_resyncs: self.num_resyncs,
+            reached_live: self.reached_live,
+            rng: self.rng,
+        }
+    }
+
+    /// replaces the [`StreamBuffer`] (which defaults to [`LastPacketBuffer`]). this is meant to be
+    /// called before the first packet is received.
+    pub fn with_buffer<B2: StreamBuffer>(
+        self,
+        stream_buffer: B2,
+    ) -> BroadcastHttp<'client, C, S, B2> {
+        BroadcastHttp {
+            client: self.client,
+            stream_fragment: self.stream_fragment,
+            keyframe_interval: self.keyframe_interval,
+            signup_fragment: self.signup_fragment,
+            sync_response: self.sync_response,
+            sync_instant: self.sync_instant,
+            stream_state: self.stream_state,
+            stream_buffer,
+            total_ticks: None,
+            retry_policy: self.retry_policy,
+            num_resyncs: self.num_resyncs,
+            reached_live: self.reached_live,
+            rng: self.rng,
+        }
+    }
+
     pub fn sync_response(&self) -> &SyncResponse {
         &self.sync_response
     }
 
-    async fn handle_start(&mut self) -> Result<Bytes, BroadcastHttpClientError<C::Error>> {
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.client.request_timeout = retry_policy.request_timeout;
+        self.retry_policy = retry_policy;
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// estimates how many fragments the stream is behind the live one.
+    fn lag_fragments(&self) -> i32 {
+        if self.keyframe_interval.is_zero() {
+            return 0;
+        }
+        let elapsed_fragments = (self.sync_instant.elapsed().as_secs_f64()
+            / self.keyframe_interval.as_secs_f64()) as i32;
+        self.sync_response.fragment + elapsed_fragments - self.stream_fragment
+    }
+
+    /// re-fetches sync and continues from the full fragment that it points at. this is what the
+    /// game does when the stream breaks.
+    async fn resync(&mut self) -> Result<(PacketType, Bytes), BroadcastHttpClientError<C::Error>> {
+        self.num_resyncs += 1;
+        log::debug!("resyncing (attempt {})", self.num_resyncs);
+
+        let sync_response = self.client.get_sync().await?;
+        self.stream_fragment = sync_response.fragment;
+        self.keyframe_interval = Duration::from_secs(sync_response.keyframe_interval as u64);
+        self.sync_response = sync_response;
+        self.sync_instant = Instant::now();
+
+        self.stream_state = StreamState::Fullframe;
+        log::debug!("entering state: {:?}", self.stream_state);
+
+        self.handle_fullframe().await
+    }
+
+    /// returns fragment that contains the given tick. each fragment spans `keyframe_interval`
+    /// seconds; sync response tells which tick the live fragment starts at, everything else is
+    /// derived from that.
+    pub fn fragment_for_tick(&self, tick: i32) -> i32 {
+        let ticks_per_fragment = self.sync_response.tps * self.sync_response.keyframe_interval;
+        if ticks_per_fragment <= 0 {
+            return self.sync_response.fragment;
+        }
+        let fragment = self.sync_response.fragment
+            + (tick - self.sync_response.tick).div_euclid(ticks_per_fragment);
+        fragment.max(self.signup_fragment)
+    }
+
+    async fn handle_start(
+        &mut self,
+    ) -> Result<(PacketType, Bytes), BroadcastHttpClientError<C::Error>> {
         // bool CDemoStreamHttp::OnSync( int nResync )
         // DevMsg( "Broadcast: Buffering stream tick %d fragment %d signup fragment %d\n", m_SyncResponse.nStartTick, m_SyncResponse.nSignupFragment, m_SyncResponse.nSignupFragment );
         // m_nState = STATE_START;
@@ -272,10 +567,12 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
         self.stream_state = StreamState::Fullframe;
         log::debug!("entering state: {:?}", self.stream_state);
 
-        Ok(stream_signup)
+        Ok((PacketType::Start, stream_signup))
     }
 
-    async fn handle_fullframe(&mut self) -> Result<Bytes, BroadcastHttpClientError<C::Error>> {
+    async fn handle_fullframe(
+        &mut self,
+    ) -> Result<(PacketType, Bytes), BroadcastHttpClientError<C::Error>> {
         let full = self
             .client
             .get_fragment(self.stream_fragment, FragmentType::Full)
@@ -288,10 +585,12 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
         };
         log::debug!("entering state: {:?}", self.stream_state);
 
-        Ok(full)
+        Ok((PacketType::Full, full))
     }
 
-    async fn handle_deltaframes(&mut self) -> Result<Bytes, BroadcastHttpClientError<C::Error>> {
+    async fn handle_deltaframes(
+        &mut self,
+    ) -> Result<(PacketType, Bytes), BroadcastHttpClientError<C::Error>> {
         // NOTE: loop simply allows to avoid going recursive, which is problematic in async context
         // and cannot be done without boxed futures.
         loop {
@@ -317,10 +616,17 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
                 }
 
                 let sleep_dur = fetch_after.duration_since(Instant::now());
-                #[cfg(feature = "tokio")]
-                tokio::time::sleep(sleep_dur).await;
-                #[cfg(not(feature = "tokio"))]
-                compile_error!("AAAAGH! can't sleep");
+                self.client.sleep.sleep(sleep_dur).await;
+            }
+
+            if self.reached_live
+                && self.num_resyncs < self.retry_policy.max_resyncs
+                && self
+                    .retry_policy
+                    .max_lag_fragments
+                    .is_some_and(|max_lag| self.lag_fragments() > max_lag as i32)
+            {
+                return self.resync().await;
             }
 
             let start = Instant::now();
@@ -335,6 +641,8 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
                     // fragment and delta framgnet, are needed; otherwise it'll not be possible to
                     // parse packet entities.
                     self.stream_fragment += 1;
+                    self.num_resyncs = 0;
+                    self.reached_live |= self.stream_fragment >= self.sync_response.fragment;
                     self.stream_state = StreamState::Deltaframes {
                         num_retries: 0,
                         fetch_after: start + self.keyframe_interval,
@@ -342,20 +650,34 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
                     };
                     log::debug!("entering state: {:?}", self.stream_state);
 
-                    return Ok(delta);
+                    return Ok((PacketType::Delta, delta));
                 }
 
                 Err(BroadcastHttpClientError::StatusCode(http::StatusCode::NOT_FOUND)) => {
-                    if num_retries >= MAX_DELTAFRAME_RETRIES {
+                    if num_retries >= self.retry_policy.max_retries {
                         return Err(BroadcastHttpClientError::StatusCode(
                             http::StatusCode::NOT_FOUND,
                         ));
                     }
 
+                    let behind_live = self.stream_fragment < self.sync_response.fragment;
+                    self.reached_live |= !behind_live;
+
+                    let backoff = self.retry_policy.backoff(
+                        num_retries,
+                        self.keyframe_interval,
+                        &mut self.rng,
+                    );
+                    // NOTE: fragments that precede the live one (the one that sync response
+                    // pointed at) should be available, stream stays in catchup mode for them, but
+                    // retries still must back off instead of hammering the server.
+                    if behind_live {
+                        self.client.sleep.sleep(backoff).await;
+                    }
                     self.stream_state = StreamState::Deltaframes {
                         num_retries: num_retries + 1,
-                        fetch_after: start + self.keyframe_interval,
-                        catchup: false,
+                        fetch_after: start + backoff,
+                        catchup: behind_live,
                     };
                     log::debug!("entering state: {:?}", self.stream_state);
 
@@ -372,35 +694,41 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
     pub async fn next_packet(
         &mut self,
     ) -> Option<Result<Bytes, BroadcastHttpClientError<C::Error>>> {
-        match match self.stream_state {
+        let mut result = match self.stream_state {
             StreamState::Stop => return None,
             StreamState::Start => self.handle_start().await,
             StreamState::Fullframe => self.handle_fullframe().await,
             StreamState::Deltaframes { .. } => self.handle_deltaframes().await,
-        } {
-            Ok(packet) => {
-                match self.stream_buffer {
-                    StreamBuffer::Last(ref mut value) => {
-                        use bytes::Buf;
-
-                        *value = Some(
-                            packet
-                                // NOTE: clone is not cloning underlying bytes, but just increases
-                                // ref count.
-                                .clone()
-                                .reader(),
-                        );
-                    }
-                    StreamBuffer::Seekable(ref mut cursor) => {
-                        cursor
-                            .write_all(packet.as_ref())
-                            // TODO: this should not panic. probably it's fine. there are very few
-                            // things that could go wrong with Vec<u8> in rust.
-                            .expect("could not buffer");
-                        // invalidate last tick so that it can be re-scanned if needed.
-                        self.total_ticks = None;
+        };
+        // NOTE: full and delta fragments (and the sync that resync itself requests) can fail with
+        // transient errors; stream is re-synced until it succeeds or resyncs are exhausted. there's
+        // nothing to resync to before the start fragment is received.
+        while let Err(err) = &result {
+            if !err.is_transient()
+                || self.num_resyncs >= self.retry_policy.max_resyncs
+                || matches!(self.stream_state, StreamState::Start)
+            {
+                break;
+            }
+            log::debug!("{err}");
+            result = self.resync().await;
+        }
+
+        match result {
+            Ok((packet_type, packet)) => {
+                if packet_type == PacketType::Delta && self.stream_buffer.needs_full() {
+                    // NOTE: stream_fragment was incremented after receiving the delta.
+                    let fragment = self.stream_fragment - 1;
+                    match self.client.get_fragment(fragment, FragmentType::Full).await {
+                        Ok(full) => self.stream_buffer.splice_full(full),
+                        // NOTE: this is not fatal, current group will grow a bit longer;
+                        // there'll be another attempt with next delta.
+                        Err(err) => log::warn!("could not fetch full fragment {fragment}: {err}"),
                     }
                 }
+                self.stream_buffer.push(packet_type, &packet);
+                // invalidate last tick so that it can be re-scanned if needed.
+                self.total_ticks = None;
 
                 Some(Ok(packet))
             }
@@ -417,165 +745,803 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
     }
 }
 
+impl<'client, C: HttpClient + 'client, B: StreamBuffer> BroadcastHttp<'client, C, ThreadSleep, B> {
+    /// blocking version of [`BroadcastHttp::next_packet`], for use outside of async executors.
+    ///
+    /// constructors can be driven with [`pollster::block_on`] (re-exported as
+    /// [`crate::block_on`]), for example:
+    ///
+    /// ```ignore
+    /// let mut broadcast = block_on(BroadcastHttp::start_streaming(http_client, url))?
+    ///     .with_sleep(ThreadSleep);
+    /// while let Some(packet) = broadcast.next_packet_blocking() {
+    ///     // ...
+    /// }
+    /// ```
+    ///
+    /// NOTE: http client must not depend on an async runtime either.
+    pub fn next_packet_blocking(
+        &mut self,
+    ) -> Option<Result<Bytes, BroadcastHttpClientError<C::Error>>> {
+        pollster::block_on(self.next_packet())
+    }
+}
+
 // ----
 // demo stream
 
-// NOTE: interacting with stream (read/seek) that has not packets is a developer error, it's is
-// okay to panic.
-macro_rules! no_packet_panic {
-    () => {
-        panic!("attempted reading on BroadcastHttp with no packets")
-    };
-}
+impl<'client, C: HttpClient + 'client, S: Sleep, B: StreamBuffer> DemoStream
+    for BroadcastHttp<'client, C, S, B>
+{
+    // stream ops
+    // ----
 
-// NOTE: this is a developer error, it's is okay to panic.
-macro_rules! not_seekable_panic {
-    () => {
-        panic!("attempted invoking seek-related operation on BroadcastHttp constructed not with `start_streaming_and_buffer`")
-    };
-}
+    /// true if `next_packet` never succeeded.
+    #[inline]
+    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
+        Ok(self.stream_buffer.is_at_eof())
+    }
 
-impl<'client, C: HttpClient + 'client> DemoStream for BroadcastHttp<'client, C> {
-    // stream ops
+    // cmd header
+    // ----
+
+    /// fails with [`io::ErrorKind::UnexpectedEof`] if `next_packet` never succeeded.
+    #[inline]
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
+        self.stream_buffer.read_cmd_header()
+    }
+
+    // cmd
     // ----
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`. othwerwise
+    /// fails with [`io::ErrorKind::UnexpectedEof`] if `next_packet` never succeeded.
+    #[inline]
+    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
+        self.stream_buffer.read_cmd(cmd_header)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_send_tables(data: &[u8]) -> Result<CDemoSendTables, DecodeCmdError> {
+        decode_cmd_send_tables(data)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_class_info(data: &[u8]) -> Result<CDemoClassInfo, DecodeCmdError> {
+        decode_cmd_class_info(data)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_packet(data: &[u8]) -> Result<CDemoPacket, DecodeCmdError> {
+        decode_cmd_packet(data)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_full_packet(data: &[u8]) -> Result<CDemoFullPacket, DecodeCmdError> {
+        decode_cmd_full_packet(data)
+    }
+
+    #[inline]
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.stream_buffer.skip_cmd(cmd_header)
+    }
+}
+
+impl<'client, C: HttpClient + 'client, S: Sleep, B: SeekableStreamBuffer> SeekableDemoStream
+    for BroadcastHttp<'client, C, S, B>
+{
     /// delegated to [`std::io::Cursor`].
+    #[inline]
     fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(ref mut c) => c.seek(pos),
-        }
+        self.stream_buffer.cursor_mut().seek(pos)
     }
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`.
+    #[inline]
     fn stream_position(&mut self) -> Result<u64, io::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(ref mut c) => Ok(c.position()),
-        }
+        Ok(self.stream_buffer.cursor().position())
     }
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`.
+    #[inline]
     fn stream_len(&mut self) -> Result<u64, io::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(ref c) => Ok(c.get_ref().len() as u64),
-        }
+        Ok(self.stream_buffer.cursor().get_ref().len() as u64)
     }
 
-    /// panics if `next_packet` never succeded.
-    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(None) => no_packet_panic!(),
-            StreamBuffer::Last(Some(ref r)) => Ok(!r.get_ref().has_remaining()),
-            StreamBuffer::Seekable(ref c) => Ok(c.position() as usize >= c.get_ref().len()),
+    fn start_position(&self) -> u64 {
+        0
+    }
+
+    fn total_ticks(&mut self) -> Result<i32, anyhow::Error> {
+        if let Some(total_ticks) = self.total_ticks {
+            return Ok(total_ticks);
         }
+        let total_ticks = scan_for_last_tick(self)?;
+        self.total_ticks = Some(total_ticks);
+        Ok(total_ticks)
     }
+}
 
-    // cmd header
-    // ----
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
 
-    /// panics if `next_packet` never succeded.
-    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
-        match self.stream_buffer {
-            StreamBuffer::Last(None) => no_packet_panic!(),
-            StreamBuffer::Last(Some(ref mut r)) => read_cmd_header(r),
-            StreamBuffer::Seekable(ref mut c) => read_cmd_header(c),
+    use haste_core::demofile::DemoFile;
+    use haste_core::fieldvalue::FieldValue;
+    use haste_core::parser::Parser;
+    use haste_core::testdemo::{
+        HEALTH, ROUND, TEST_ENTITY_CLASS_ID, TEST_GAME_RULES_CLASS_ID, TestDemoBuilder,
+        entities_state,
+    };
+    use pollster::block_on;
+
+    use super::*;
+    use crate::broadcastserver::{BroadcastServer, BroadcastServerOptions};
+    use crate::recordedhttpclient::{
+        RecordedHttpClient, RecordedHttpClientError, RecordedResponse,
+    };
+
+    const BASE_URL: &str = "http://localhost/tv/1";
+
+    // NOTE: tests are driven by pollster's block_on; DefaultSleep (which is TokioSleep when tokio
+    // feature is enabled) needs tokio runtime, thus all streams use ThreadSleep (or RecordingSleep).
+
+    // NOTE: keyframe interval is 0 so that retries do not need to wait.
+    fn sync_response(fragment: i32) -> SyncResponse {
+        SyncResponse {
+            tick: 0,
+            endtick: 0,
+            maxtick: 0,
+            rtdelay: 0.0,
+            rcvage: 0.0,
+            fragment,
+            signup_fragment: 0,
+            tps: 60,
+            keyframe_interval: 0,
+            map: "start".to_string(),
+            protocol: 5,
         }
     }
 
-    // cmd
-    // ----
+    fn recorded_http_client(
+        sync_response: &SyncResponse,
+    ) -> Result<RecordedHttpClient, serde_json::Error> {
+        let client = RecordedHttpClient::new();
+        client.push_response(
+            "sync",
+            RecordedResponse::Ok(serde_json::to_vec(sync_response)?.into()),
+        );
+        client.push_response(
+            format!("{}/start", sync_response.signup_fragment),
+            RecordedResponse::Ok(Bytes::from_static(b"start")),
+        );
+        Ok(client)
+    }
 
-    /// panics if `next_packet` never succeded.
-    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
-        match self.stream_buffer {
-            StreamBuffer::Last(None) => no_packet_panic!(),
+    fn ok(body: &'static [u8]) -> RecordedResponse {
+        RecordedResponse::Ok(Bytes::from_static(body))
+    }
+
+    #[test]
+    fn test_start_fullframe_deltaframes() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(3))?;
+        client.push_response("3/full", ok(b"full3"));
+        client.push_response("3/delta", ok(b"delta3"));
+        client.push_response("4/delta", ok(b"delta4"));
+
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming(&client, BASE_URL)
+                .await?
+                .with_sleep(ThreadSleep);
+            assert_eq!(stream.sync_response().fragment, 3);
+
+            let mut packets = Vec::new();
+            while let Some(packet) = stream.next_packet().await {
+                packets.push(packet?);
+            }
+            assert_eq!(packets, ["start", "full3", "delta3", "delta4"]);
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        // NOTE: 5/delta is not available; initial attempt + retries.
+        let mut expected = ["sync", "0/start", "3/full", "3/delta", "4/delta"]
+            .map(String::from)
+            .to_vec();
+        expected.extend((0..=MAX_DELTAFRAME_RETRIES).map(|_| "5/delta".to_string()));
+        assert_eq!(client.requests(), expected);
+
+        Ok(())
+    }
 
-            StreamBuffer::Last(Some(ref mut r)) => {
-                use bytes::Buf;
+    #[test]
+    fn test_deltaframes_retry_and_catchup() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        client.push_response("0/full", ok(b"full0"));
+        client.push_response("0/delta", ok(b"delta0"));
+        // fragment that is not available yet
+        for _ in 0..MAX_DELTAFRAME_RETRIES {
+            client.push_response(
+                "1/delta",
+                RecordedResponse::Status(http::StatusCode::NOT_FOUND),
+            );
+        }
+        client.push_response("1/delta", ok(b"delta1"));
+        client.push_response("2/delta", ok(b"delta2"));
+
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming(&client, BASE_URL)
+                .await?
+                .with_sleep(ThreadSleep);
+            for expected in ["start", "full0", "delta0", "delta1", "delta2"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        let requests = client.requests();
+        assert_eq!(
+            requests.iter().filter(|key| *key == "1/delta").count() as u32,
+            MAX_DELTAFRAME_RETRIES + 1
+        );
+        // catching up: fragment that follows successful one is requested right away.
+        assert_eq!(requests.last().map(String::as_str), Some("2/delta"));
+
+        Ok(())
+    }
 
-                let size = cmd_header.body_size as usize;
-                let bytes = r.get_mut();
+    #[test]
+    fn test_resync_on_http_client_error() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        client.push_response(
+            "sync",
+            RecordedResponse::Ok(serde_json::to_vec(&sync_response(1))?.into()),
+        );
+        client.push_response("0/full", ok(b"full0"));
+        client.push_response("0/delta", RecordedResponse::Timeout);
+        client.push_response("1/full", ok(b"full1"));
+        client.push_response("1/delta", ok(b"delta1"));
+
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming(&client, BASE_URL)
+                .await?
+                .with_sleep(ThreadSleep);
+            for expected in ["start", "full0", "full1", "delta1"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        assert_eq!(
+            client.requests(),
+            [
+                "sync", "0/start", "0/full", "0/delta", "sync", "1/full", "1/delta"
+            ]
+        );
+        Ok(())
+    }
 
-                // it probably could be possible that body of the response was not transferred /
-                // read correctly?
-                if bytes.remaining() < size {
-                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
-                }
+    #[test]
+    fn test_bad_gzip_body() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        client.push_response("0/full", RecordedResponse::BadGzip);
+
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming(&client, BASE_URL)
+                .await?
+                .with_sleep(ThreadSleep);
+            stream.next_packet().await.transpose()?;
+            assert!(matches!(
+                stream.next_packet().await,
+                Some(Err(BroadcastHttpClientError::HttpClientError(
+                    RecordedHttpClientError::BadGzip
+                )))
+            ));
+            assert!(stream.next_packet().await.is_none());
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        // NOTE: initial attempt + resyncs.
+        let requests = client.requests();
+        assert_eq!(
+            requests.iter().filter(|key| *key == "0/full").count() as u32,
+            MAX_RESYNCS + 1
+        );
+        Ok(())
+    }
 
-                // SAFETY: this is safe because lifetime of the returned slice is tied to the
-                // lifetime of r (if i'm not missing anything, am i?).
-                let data = unsafe {
-                    // NOTE: start is 0 because Reader's advance will increase start position of
-                    // the underlying slice
-                    let ptr = bytes.as_ref()[0..size].as_ptr();
-                    std::slice::from_raw_parts(ptr, size)
-                };
-                bytes.advance(size);
-                Ok(data)
+    #[test]
+    fn test_start_streaming_from_beginning() -> Result<(), anyhow::Error> {
+        // NOTE: live fragment is 9, but stream must start at signup fragment and fast-forward
+        // through fragments that are available (even if they respond with 404 once).
+        let client = recorded_http_client(&sync_response(9))?;
+        client.push_response("0/full", ok(b"full0"));
+        client.push_response("0/delta", ok(b"delta0"));
+        client.push_response(
+            "1/delta",
+            RecordedResponse::Status(http::StatusCode::NOT_FOUND),
+        );
+        client.push_response("1/delta", ok(b"delta1"));
+
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming_from_beginning(&client, BASE_URL)
+                .await?
+                .with_sleep(ThreadSleep);
+            for expected in ["start", "full0", "delta0", "delta1"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
             }
+            Ok::<_, anyhow::Error>(())
+        })
+    }
 
-            StreamBuffer::Seekable(ref mut c) => {
-                let size = cmd_header.body_size as usize;
-                let pos = c.position() as usize;
+    #[test]
+    fn test_start_streaming_from_tick() -> Result<(), anyhow::Error> {
+        let sync_response = SyncResponse {
+            tick: 3000,
+            fragment: 10,
+            signup_fragment: 2,
+            keyframe_interval: 3,
+            ..sync_response(10)
+        };
+        let client = recorded_http_client(&sync_response)?;
+        client.push_response("5/full", ok(b"full5"));
+
+        block_on(async {
+            let mut stream =
+                BroadcastHttp::start_streaming_from_tick(&client, BASE_URL, 3000 - 180 * 5)
+                    .await?
+                    .with_sleep(ThreadSleep);
+            assert_eq!(stream.fragment_for_tick(3000), 10);
+            assert_eq!(stream.fragment_for_tick(3179), 10);
+            assert_eq!(stream.fragment_for_tick(2999), 9);
+            assert_eq!(stream.fragment_for_tick(0), 2);
+            for expected in ["start", "full5"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
 
-                // it probably could be possible that body of the response was not transferred /
-                // read correctly?
-                let remaining = c.get_ref().len() - pos;
-                if remaining < size {
-                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
-                }
+        assert_eq!(client.requests(), ["sync", "2/start", "5/full"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resync_on_server_error() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        client.push_response(
+            "sync",
+            RecordedResponse::Ok(serde_json::to_vec(&sync_response(5))?.into()),
+        );
+        client.push_response("0/full", ok(b"full0"));
+        client.push_response("0/delta", ok(b"delta0"));
+        client.push_response(
+            "1/delta",
+            RecordedResponse::Status(http::StatusCode::SERVICE_UNAVAILABLE),
+        );
+        client.push_response("5/full", ok(b"full5"));
+        client.push_response("5/delta", ok(b"delta5"));
+
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming(&client, BASE_URL)
+                .await?
+                .with_sleep(ThreadSleep);
+            for expected in ["start", "full0", "delta0", "full5", "delta5"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        assert_eq!(
+            client.requests(),
+            [
+                "sync", "0/start", "0/full", "0/delta", "1/delta", "sync", "5/full", "5/delta"
+            ]
+        );
+        Ok(())
+    }
 
-                // NOTE: Cursor's advance will not discard data from the underlying Vec<u8>
-                c.consume(size);
-                Ok(&c.get_ref()[pos..pos + size])
+    #[test]
+    fn test_resync_on_full_server_error() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        client.push_response(
+            "sync",
+            RecordedResponse::Ok(serde_json::to_vec(&sync_response(2))?.into()),
+        );
+        client.push_response(
+            "0/full",
+            RecordedResponse::Status(http::StatusCode::INTERNAL_SERVER_ERROR),
+        );
+        client.push_response("2/full", ok(b"full2"));
+        client.push_response("2/delta", ok(b"delta2"));
+
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming(&client, BASE_URL)
+                .await?
+                .with_sleep(ThreadSleep);
+            for expected in ["start", "full2", "delta2"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
             }
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        assert_eq!(
+            client.requests(),
+            ["sync", "0/start", "0/full", "sync", "2/full", "2/delta"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resync_gives_up() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        client.push_response(
+            "sync",
+            RecordedResponse::Ok(serde_json::to_vec(&sync_response(1))?.into()),
+        );
+        client.push_response("0/full", ok(b"full0"));
+        client.push_response("0/delta", ok(b"delta0"));
+        client.push_response("1/full", ok(b"full1"));
+        client.push_response(
+            "1/delta",
+            RecordedResponse::Status(http::StatusCode::BAD_GATEWAY),
+        );
+
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming(&client, BASE_URL)
+                .await?
+                .with_sleep(ThreadSleep);
+            stream.set_retry_policy(RetryPolicy {
+                max_resyncs: 1,
+                ..Default::default()
+            });
+            for expected in ["start", "full0", "delta0", "full1"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
+            }
+            assert!(matches!(
+                stream.next_packet().await,
+                Some(Err(BroadcastHttpClientError::StatusCode(
+                    http::StatusCode::BAD_GATEWAY
+                )))
+            ));
+            assert!(stream.next_packet().await.is_none());
+            Ok::<_, anyhow::Error>(())
+        })
+    }
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let mut rng = Rng::new();
+        let retry_policy = RetryPolicy {
+            initial_backoff: Some(Duration::from_secs(1)),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let backoffs: Vec<u64> = (0..4)
+            .map(|num_retries| {
+                retry_policy
+                    .backoff(num_retries, Duration::ZERO, &mut rng)
+                    .as_secs()
+            })
+            .collect();
+        assert_eq!(backoffs, [1, 2, 4, 5]);
+
+        // default follows keyframe interval
+        assert_eq!(
+            RetryPolicy::default().backoff(3, Duration::from_secs(3), &mut rng),
+            Duration::from_secs(3)
+        );
+
+        let retry_policy = RetryPolicy {
+            initial_backoff: Some(Duration::from_secs(2)),
+            jitter: 0.5,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let backoff = retry_policy.backoff(0, Duration::ZERO, &mut rng);
+            assert!(backoff >= Duration::from_secs(1) && backoff <= Duration::from_secs(3));
         }
+
+        // huge multipliers and exponents (including num_retries past i32::MAX, which must not
+        // wrap into a negative exponent) and jitter are clamped to max backoff
+        let retry_policy = RetryPolicy {
+            initial_backoff: Some(Duration::from_secs(1)),
+            backoff_multiplier: 1e300,
+            max_backoff: Duration::from_secs(5),
+            jitter: 1.0,
+            ..Default::default()
+        };
+        for num_retries in [1, 100, u32::MAX] {
+            let backoff = retry_policy.backoff(num_retries, Duration::ZERO, &mut rng);
+            assert!(backoff <= Duration::from_secs(5));
+        }
+        let retry_policy = RetryPolicy {
+            initial_backoff: Some(Duration::from_secs(1)),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            retry_policy.backoff(u32::MAX, Duration::ZERO, &mut rng),
+            Duration::from_secs(5)
+        );
+        let retry_policy = RetryPolicy {
+            backoff_multiplier: f64::INFINITY,
+            max_backoff: Duration::MAX,
+            ..Default::default()
+        };
+        assert_eq!(
+            retry_policy.backoff(3, Duration::from_secs(1), &mut rng),
+            Duration::MAX
+        );
     }
 
-    #[inline(always)]
-    fn decode_cmd_send_tables(data: &[u8]) -> Result<CDemoSendTables, DecodeCmdError> {
-        decode_cmd_send_tables(data)
+    #[derive(Default)]
+    struct RecordingSleep(std::sync::Mutex<Vec<Duration>>);
+
+    impl Sleep for &RecordingSleep {
+        async fn sleep(&self, duration: Duration) {
+            self.0
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push(duration);
+        }
     }
 
-    #[inline(always)]
-    fn decode_cmd_class_info(data: &[u8]) -> Result<CDemoClassInfo, DecodeCmdError> {
-        decode_cmd_class_info(data)
+    #[test]
+    fn test_sleep() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&SyncResponse {
+            keyframe_interval: 1,
+            ..sync_response(0)
+        })?;
+        client.push_response("0/full", ok(b"full0"));
+        client.push_response("0/delta", ok(b"delta0"));
+        client.push_response(
+            "1/delta",
+            RecordedResponse::Status(http::StatusCode::NOT_FOUND),
+        );
+        client.push_response("1/delta", ok(b"delta1"));
+
+        let sleep = RecordingSleep::default();
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming(&client, BASE_URL)
+                .await?
+                .with_sleep(&sleep);
+            for expected in ["start", "full0", "delta0", "delta1"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        // NOTE: fragment that is not available is re-requested after keyframe interval.
+        let sleeps = sleep.0.into_inner().unwrap_or_else(|err| err.into_inner());
+        assert_eq!(sleeps.len(), 1);
+        assert!(sleeps[0] > Duration::from_millis(900) && sleeps[0] <= Duration::from_secs(1));
+        Ok(())
     }
 
-    #[inline(always)]
-    fn decode_cmd_packet(data: &[u8]) -> Result<CDemoPacket, DecodeCmdError> {
-        decode_cmd_packet(data)
+    #[test]
+    fn test_sleep_while_catching_up() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(3))?;
+        client.push_response("0/full", ok(b"full0"));
+        client.push_response("0/delta", ok(b"delta0"));
+        client.push_response(
+            "1/delta",
+            RecordedResponse::Status(http::StatusCode::NOT_FOUND),
+        );
+        client.push_response("1/delta", ok(b"delta1"));
+
+        let sleep = RecordingSleep::default();
+        block_on(async {
+            let mut stream = BroadcastHttp::start_streaming_from_beginning(&client, BASE_URL)
+                .await?
+                .with_sleep(&sleep);
+            stream.set_retry_policy(RetryPolicy {
+                initial_backoff: Some(Duration::from_secs(2)),
+                ..Default::default()
+            });
+            for expected in ["start", "full0", "delta0", "delta1"] {
+                assert_eq!(
+                    stream.next_packet().await.transpose()?,
+                    Some(Bytes::from(expected))
+                );
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        // NOTE: fragment behind the live one is retried after backoff, fragments that are
+        // available are still requested right away.
+        let sleeps = sleep.0.into_inner().unwrap_or_else(|err| err.into_inner());
+        assert_eq!(sleeps, [Duration::from_secs(2)]);
+        Ok(())
     }
 
-    #[inline(always)]
-    fn decode_cmd_full_packet(data: &[u8]) -> Result<CDemoFullPacket, DecodeCmdError> {
-        decode_cmd_full_packet(data)
+    #[test]
+    fn test_next_packet_blocking() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        client.push_response("0/full", ok(b"full0"));
+
+        let mut stream =
+            block_on(BroadcastHttp::start_streaming(&client, BASE_URL))?.with_sleep(ThreadSleep);
+        let mut packets = Vec::new();
+        while let Some(packet) = stream.next_packet_blocking() {
+            packets.push(packet?);
+        }
+        assert_eq!(packets, ["start", "full0"]);
+        Ok(())
     }
 
-    // other
-    // ----
+    #[test]
+    fn test_read_before_first_packet() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        let mut stream =
+            block_on(BroadcastHttp::start_streaming(&client, BASE_URL))?.with_sleep(ThreadSleep);
+        assert!(stream.is_at_eof()?);
+        assert!(matches!(
+            stream.read_cmd_header(),
+            Err(ReadCmdHeaderError::IoError(err)) if err.kind() == io::ErrorKind::UnexpectedEof
+        ));
+        Ok(())
+    }
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`.
-    fn start_position(&self) -> u64 {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(_) => 0,
+    #[test]
+    fn test_bounded_buffer() -> Result<(), anyhow::Error> {
+        let client = recorded_http_client(&sync_response(0))?;
+        for (key, body) in [
+            ("0/full", b"F0"),
+            ("2/full", b"F2"),
+            ("4/full", b"F4"),
+            ("0/delta", b"d0"),
+            ("1/delta", b"d1"),
+            ("2/delta", b"d2"),
+            ("3/delta", b"d3"),
+            ("4/delta", b"d4"),
+        ] {
+            client.push_response(key, ok(body));
         }
+
+        let options = BoundedBufferOptions {
+            max_full_frames: 1,
+            fragments_per_full_frame: 2,
+        };
+        block_on(async {
+            let mut stream =
+                BroadcastHttp::start_streaming_and_buffer_bounded(&client, BASE_URL, options)
+                    .await?
+                    .with_sleep(ThreadSleep);
+            for _ in 0..7 {
+                stream.next_packet().await.transpose()?;
+            }
+            assert_eq!(stream.stream_buffer.cursor().get_ref(), b"startF4d4");
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        assert_eq!(
+            client.requests(),
+            [
+                "sync", "0/start", "0/full", "0/delta", "1/delta", "2/delta", "2/full", "3/delta",
+                "4/delta", "4/full"
+            ]
+        );
+        Ok(())
     }
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`.
-    fn total_ticks(&mut self) -> Result<i32, anyhow::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(_) => {
-                if self.total_ticks.is_none() {
-                    self.total_ticks = Some(scan_for_last_tick(self)?);
-                }
-                Ok(self.total_ticks.unwrap())
+    #[test]
+    fn test_run_to_tick_bounded_buffer() -> Result<(), anyhow::Error> {
+        // packets each 30 ticks, full packets at ticks 10 and 1810; last tick is 2380.
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .create_entity(
+                1,
+                TEST_GAME_RULES_CLASS_ID,
+                1,
+                &[(ROUND, FieldValue::U64(1))],
+            )
+            .create_entity(2, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(10)?
+            .write_full_packet(10)?;
+        for tick in (40..2400).step_by(30) {
+            builder.update_entity(2, &[(HEALTH, FieldValue::I64(tick as i64))]);
+            if tick % 600 == 10 {
+                builder.update_entity(1, &[(ROUND, FieldValue::U64(tick as u64))]);
+            }
+            builder.write_packet(tick)?;
+            if tick == 1810 {
+                builder.write_full_packet(tick)?;
             }
         }
+        let data = builder.finish(2400)?;
+
+        // fragments span 60 ticks, fragment k starts at tick 10 + 60 * k.
+        let mut server = BroadcastServer::new(
+            DemoFile::start_reading(Cursor::new(&data))?,
+            BroadcastServerOptions {
+                keyframe_interval: 2,
+                speed: f64::INFINITY,
+            },
+        )?;
+        let client = RecordedHttpClient::new();
+        let mut keys = vec!["sync".to_string(), "0/start".to_string()];
+        for fragment in 0..server.num_fragments() {
+            keys.push(format!("{fragment}/full"));
+            keys.push(format!("{fragment}/delta"));
+        }
+        for key in keys {
+            let response = server.respond(&format!("/tv/1/{key}"));
+            assert_eq!(response.status, 200, "{key}");
+            client.push_response(key, RecordedResponse::Ok(response.body.into()));
+        }
+
+        // retains 2 groups of 4 fragments, fragments 32..=39; that's [1930, 2380].
+        let options = BoundedBufferOptions {
+            max_full_frames: 2,
+            fragments_per_full_frame: 4,
+        };
+        let mut stream = block_on(BroadcastHttp::start_streaming_from_beginning(
+            &client, BASE_URL,
+        ))?
+        .with_sleep(ThreadSleep)
+        .with_buffer(BoundedBuffer::new(options));
+        stream.set_retry_policy(RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        });
+        while let Some(packet) = stream.next_packet_blocking() {
+            packet?;
+        }
+
+        let mut parser = Parser::from_stream(stream)?;
+        let mut demo_parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&data))?)?;
+        for tick in [2290, 2140, 2380] {
+            parser.run_to_tick(tick)?;
+            demo_parser.run_to_tick(tick)?;
+            assert_eq!(parser.context().tick(), tick);
+            let entities = entities_state(parser.context());
+            assert_eq!(entities.len(), 2);
+            assert_eq!(entities, entities_state(demo_parser.context()));
+        }
+        // NOTE: seeking must not require a scan for the last tick.
+        assert!(parser.demo_stream().total_ticks.is_none());
+
+        // outside of the retained window
+        assert!(
+            parser
+                .run_to_tick(100)
+                .is_err_and(|err| err.to_string().contains("precedes the first tick"))
+        );
+        assert!(
+            parser
+                .run_to_tick(2381)
+                .is_err_and(|err| err.to_string().contains("past the last tick"))
+        );
+
+        parser.run_to_tick(2200)?;
+        demo_parser.run_to_tick(2200)?;
+        assert_eq!(
+            entities_state(parser.context()),
+            entities_state(demo_parser.context())
+        );
+
+        Ok(())
     }
 }
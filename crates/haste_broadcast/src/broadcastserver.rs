@@ -0,0 +1,685 @@
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Instant;
+
+use haste_core::democlip::{for_each_packet_msg, StateSnapshot};
+use haste_core::demofile::DemoFile;
+use haste_core::demostream::{DemoStream, SeekableDemoStream};
+use prost::Message;
+use valveprotos::common::{
+    CDemoFileHeader, CDemoPacket, CsvcMsgServerInfo, EDemoCommands, SvcMessages,
+};
+
+use crate::broadcasthttp::SyncResponse;
+use crate::demostream::{encode_cmd_send_tables, write_cmd_header};
+
+// NOTE: the server is not a replica of valve's relay, it only mimics things that BroadcastHttp
+// (and, hopefully, the game) rely on:
+// - `/sync` responds with json (see SyncResponse);
+// - `/<signup_fragment>/start` contains signon cmds;
+// - `/<fragment>/full` contains state at the beginning of the fragment (synthesized, see
+//   haste_core::democlip::StateSnapshot; string tables come from the last full packet's snapshot
+//   rather than from their whole history);
+// - `/<fragment>/delta` contains packets within the fragment.
+//
+// signup fragment is always 0. fragments are not "live" until the clock reaches them; requests
+// for fragments that are in the future result in 404 (same as with valve's relay).
+
+const SIGNUP_FRAGMENT: i32 = 0;
+
+pub struct BroadcastServerOptions {
+    /// the interval between full keyframes, in seconds (/ duration of a fragment).
+    pub keyframe_interval: u32,
+    /// playback speed; 1.0 is real-time. [`f64::INFINITY`] makes all fragments available
+    /// immediately.
+    pub speed: f64,
+}
+
+impl Default for BroadcastServerOptions {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: 3,
+            speed: 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BroadcastServerResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl BroadcastServerResponse {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    fn not_found() -> Self {
+        Self {
+            status: 404,
+            content_type: "text/plain",
+            body: b"not found".to_vec(),
+        }
+    }
+
+    fn internal_server_error(err: anyhow::Error) -> Self {
+        Self {
+            status: 500,
+            content_type: "text/plain",
+            body: err.to_string().into_bytes(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            404 => "Not Found",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+struct Fragment {
+    start_tick: i32,
+    /// position of the first cmd of the fragment in the demo file. first fragment starts right
+    /// after signon.
+    offset: u64,
+}
+
+/// state snapshot that was built up to (not including) `fragment`. full fragments are
+/// usually requested in ascending order, there's no need to rebuild the snapshot from scratch each
+/// time.
+struct SnapshotCursor {
+    state_snapshot: StateSnapshot,
+    fragment: usize,
+}
+
+/// serves a replay as an http broadcast (that can be consumed with [`crate::BroadcastHttp`]).
+pub struct BroadcastServer<R: Read + Seek> {
+    demo_file: DemoFile<R>,
+    options: BroadcastServerOptions,
+    map: String,
+    protocol: i32,
+    tps: i32,
+    fragment_ticks: i32,
+    start: Vec<u8>,
+    /// signon packets; string tables that are created within signon are needed to build full
+    /// fragments (see [`StateSnapshot::handle_signon_packet`]).
+    signon_packets: Vec<CDemoPacket>,
+    fragments: Vec<Fragment>,
+    /// position past the last cmd of the last fragment.
+    end_offset: u64,
+    snapshot_cursor: Option<SnapshotCursor>,
+    started_at: Option<Instant>,
+}
+
+impl<R: Read + Seek> BroadcastServer<R> {
+    /// prepares (indexes) the replay for serving. nothing is being served until
+    /// [`BroadcastServer::serve`] is called.
+    pub fn new(
+        mut demo_file: DemoFile<R>,
+        options: BroadcastServerOptions,
+    ) -> Result<Self, anyhow::Error> {
+        let mut map = String::new();
+        let mut protocol = 0;
+        let mut tick_interval: Option<f32> = None;
+        let mut start = Vec::new();
+        let mut signon_packets = Vec::new();
+
+        // signon
+        demo_file.seek(SeekFrom::Start(demo_file.start_position()))?;
+        loop {
+            let cmd_header = demo_file.read_cmd_header()?;
+            if cmd_header.cmd == EDemoCommands::DemSyncTick {
+                break;
+            }
+
+            let body = demo_file.read_cmd(&cmd_header)?;
+            let body = match cmd_header.cmd {
+                EDemoCommands::DemFileHeader => {
+                    let cmd = CDemoFileHeader::decode(body)?;
+                    map = cmd.map_name().to_string();
+                    protocol = cmd.network_protocol();
+                    continue;
+                }
+                EDemoCommands::DemSignonPacket | EDemoCommands::DemPacket => {
+                    let cmd = DemoFile::<R>::decode_cmd_packet(body)?;
+                    for_each_packet_msg(cmd.data(), |msg_type, data| {
+                        if msg_type == SvcMessages::SvcServerInfo as u32 {
+                            let msg = CsvcMsgServerInfo::decode(data)?;
+                            tick_interval = tick_interval.or(msg.tick_interval);
+                            if map.is_empty() {
+                                map = msg.map_name().to_string();
+                            }
+                        }
+                        Ok(())
+                    })?;
+                    let data = cmd.data().to_vec();
+                    signon_packets.push(cmd);
+                    data
+                }
+                EDemoCommands::DemSendTables => {
+                    encode_cmd_send_tables(&DemoFile::<R>::decode_cmd_send_tables(body)?)
+                }
+                EDemoCommands::DemClassInfo => body.to_vec(),
+                _ => continue,
+            };
+            write_cmd_header(
+                &mut start,
+                cmd_header.cmd,
+                cmd_header.tick,
+                body.len() as u32,
+            )?;
+            start.extend_from_slice(&body);
+        }
+
+        let Some(tick_interval) = tick_interval else {
+            anyhow::bail!("could not find tick interval (server info is missing)");
+        };
+        let tps = (1.0 / tick_interval).round() as i32;
+        let fragment_ticks = (options.keyframe_interval as i32 * tps).max(1);
+
+        // fragments
+        let mut fragments: Vec<Fragment> = Vec::new();
+        let mut first_tick: Option<i32> = None;
+        let end_offset = loop {
+            let offset = demo_file.stream_position()?;
+            let cmd_header = match demo_file.read_cmd_header() {
+                Ok(cmd_header) => cmd_header,
+                Err(_) if demo_file.is_at_eof().unwrap_or_default() => break offset,
+                Err(err) => return Err(err.into()),
+            };
+            if cmd_header.cmd == EDemoCommands::DemStop {
+                break offset;
+            }
+
+            let first_tick = *first_tick.get_or_insert(cmd_header.tick);
+            let fragment = ((cmd_header.tick - first_tick).max(0) / fragment_ticks) as usize;
+            while fragments.len() <= fragment {
+                fragments.push(Fragment {
+                    start_tick: first_tick + fragments.len() as i32 * fragment_ticks,
+                    offset,
+                });
+            }
+
+            demo_file.skip_cmd(&cmd_header)?;
+        };
+
+        Ok(Self {
+            demo_file,
+            options,
+            map,
+            protocol,
+            tps,
+            fragment_ticks,
+            start,
+            signon_packets,
+            fragments,
+            end_offset,
+            snapshot_cursor: None,
+            started_at: None,
+        })
+    }
+
+    #[inline]
+    pub fn num_fragments(&self) -> usize {
+        self.fragments.len()
+    }
+
+    /// returns the last fragment that is available according to the clock.
+    fn current_fragment(&self) -> i32 {
+        let last_fragment = self.fragments.len().saturating_sub(1) as i32;
+        if self.options.speed.is_infinite() {
+            return last_fragment;
+        }
+
+        let elapsed = self
+            .started_at
+            .map(|started_at| started_at.elapsed().as_secs_f64())
+            .unwrap_or_default();
+        let elapsed_ticks = elapsed * self.options.speed * self.tps as f64;
+        let fragment = (elapsed_ticks / self.fragment_ticks as f64).floor() as i32;
+        fragment.min(last_fragment)
+    }
+
+    fn fragment_end_offset(&self, fragment: usize) -> u64 {
+        self.fragments
+            .get(fragment + 1)
+            .map_or(self.end_offset, |fragment| fragment.offset)
+    }
+
+    fn sync(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let fragment = self.current_fragment();
+        let tick = self
+            .fragments
+            .get(fragment as usize)
+            .map(|fragment| fragment.start_tick)
+            .unwrap_or_default();
+        let sync_response = SyncResponse {
+            tick,
+            endtick: tick + self.fragment_ticks - 1,
+            maxtick: tick + self.fragment_ticks - 1,
+            rtdelay: 0.0,
+            rcvage: 0.0,
+            fragment,
+            signup_fragment: SIGNUP_FRAGMENT,
+            tps: self.tps,
+            keyframe_interval: self.options.keyframe_interval as i32,
+            map: self.map.clone(),
+            protocol: self.protocol,
+        };
+        Ok(serde_json::to_vec(&sync_response)?)
+    }
+
+    fn delta(&mut self, fragment: usize) -> Result<Vec<u8>, anyhow::Error> {
+        let end_offset = self.fragment_end_offset(fragment);
+        self.demo_file
+            .seek(SeekFrom::Start(self.fragments[fragment].offset))?;
+
+        let mut buf = Vec::new();
+        while self.demo_file.stream_position()? < end_offset {
+            let cmd_header = self.demo_file.read_cmd_header()?;
+            match cmd_header.cmd {
+                EDemoCommands::DemPacket | EDemoCommands::DemSignonPacket => {
+                    let body = self.demo_file.read_cmd(&cmd_header)?;
+                    let cmd = DemoFile::<R>::decode_cmd_packet(body)?;
+                    let data = cmd.data();
+                    write_cmd_header(&mut buf, cmd_header.cmd, cmd_header.tick, data.len() as u32)?;
+                    buf.extend_from_slice(data);
+                }
+                // NOTE: broadcasts don't contain full packets; other cmds are not relevant.
+                _ => self.demo_file.skip_cmd(&cmd_header)?,
+            }
+        }
+        Ok(buf)
+    }
+
+    fn full(&mut self, fragment: usize) -> Result<Vec<u8>, anyhow::Error> {
+        let mut snapshot_cursor = match self.snapshot_cursor.take() {
+            Some(snapshot_cursor) if snapshot_cursor.fragment <= fragment => snapshot_cursor,
+            _ => {
+                let mut state_snapshot = StateSnapshot::new();
+                for packet in &self.signon_packets {
+                    state_snapshot.handle_signon_packet(packet)?;
+                }
+                SnapshotCursor {
+                    state_snapshot,
+                    fragment: 0,
+                }
+            }
+        };
+
+        if snapshot_cursor.fragment < fragment {
+            let start_offset = self.fragments[snapshot_cursor.fragment].offset;
+            let end_offset = self.fragments[fragment].offset;
+            self.demo_file.seek(SeekFrom::Start(start_offset))?;
+            while self.demo_file.stream_position()? < end_offset {
+                let cmd_header = self.demo_file.read_cmd_header()?;
+                match cmd_header.cmd {
+                    EDemoCommands::DemPacket | EDemoCommands::DemSignonPacket => {
+                        let body = self.demo_file.read_cmd(&cmd_header)?;
+                        let cmd = DemoFile::<R>::decode_cmd_packet(body)?;
+                        snapshot_cursor.state_snapshot.handle_packet(&cmd)?;
+                    }
+                    EDemoCommands::DemFullPacket => {
+                        let body = self.demo_file.read_cmd(&cmd_header)?;
+                        let cmd = DemoFile::<R>::decode_cmd_full_packet(body)?;
+                        snapshot_cursor.state_snapshot.handle_full_packet(&cmd)?;
+                    }
+                    _ => self.demo_file.skip_cmd(&cmd_header)?,
+                }
+            }
+            snapshot_cursor.fragment = fragment;
+        }
+
+        let packet = snapshot_cursor.state_snapshot.to_packet();
+        self.snapshot_cursor = Some(snapshot_cursor);
+
+        let data = packet.data();
+        let mut buf = Vec::with_capacity(data.len() + 10);
+        write_cmd_header(
+            &mut buf,
+            EDemoCommands::DemPacket,
+            self.fragments[fragment].start_tick,
+            data.len() as u32,
+        )?;
+        buf.extend_from_slice(data);
+        Ok(buf)
+    }
+
+    /// responds to a request. the path can contain a prefix (for example `/tv/123/sync`), only
+    /// last segments are considered.
+    pub fn respond(&mut self, path: &str) -> BroadcastServerResponse {
+        let path = path.split('?').next().unwrap_or_default();
+        let mut segments = path.trim_end_matches('/').rsplit('/');
+        let (Some(last), second_last) = (segments.next(), segments.next()) else {
+            return BroadcastServerResponse::not_found();
+        };
+
+        if last == "sync" {
+            return match self.sync() {
+                Ok(body) => BroadcastServerResponse::ok("application/json", body),
+                Err(err) => BroadcastServerResponse::internal_server_error(err),
+            };
+        }
+
+        let Some(fragment) = second_last.and_then(|segment| segment.parse::<i32>().ok()) else {
+            return BroadcastServerResponse::not_found();
+        };
+        let is_available = fragment >= 0
+            && (fragment as usize) < self.fragments.len()
+            && fragment <= self.current_fragment();
+
+        let result = match last {
+            "start" if fragment == SIGNUP_FRAGMENT => Ok(self.start.clone()),
+            "full" if is_available => self.full(fragment as usize),
+            "delta" if is_available => self.delta(fragment as usize),
+            _ => return BroadcastServerResponse::not_found(),
+        };
+        match result {
+            Ok(body) => BroadcastServerResponse::ok("application/octet-stream", body),
+            Err(err) => BroadcastServerResponse::internal_server_error(err),
+        }
+    }
+
+    fn handle_connection(&mut self, mut stream: TcpStream) -> Result<(), io::Error> {
+        let mut rdr = BufReader::new(&stream);
+
+        let mut request_line = String::new();
+        rdr.read_line(&mut request_line)?;
+        // NOTE: headers are not relevant
+        loop {
+            let mut line = String::new();
+            if rdr.read_line(&mut line)? == 0 || line == "\r\n" {
+                break;
+            }
+        }
+
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let response = self.respond(path);
+        log::debug!("{} {}", response.status, path);
+
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            response.status,
+            response.reason(),
+            response.content_type,
+            response.body.len(),
+        )?;
+        stream.write_all(&response.body)?;
+        stream.flush()
+    }
+
+    /// starts the clock and serves requests (one at a time) until an error occurs.
+    pub fn serve(&mut self, listener: &TcpListener) -> Result<(), io::Error> {
+        self.started_at.get_or_insert_with(Instant::now);
+        for stream in listener.incoming() {
+            if let Err(err) = self.handle_connection(stream?) {
+                log::warn!("could not handle connection: {err}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use haste_core::democlip::PacketBuilder;
+    use haste_core::demowriter::DemoWriter;
+    use haste_core::fieldvalue::FieldValue;
+    use haste_core::parser::Parser;
+    use haste_core::testdemo::{
+        HEALTH, ROUND, TEST_ENTITY_CLASS_ID, TEST_GAME_RULES_CLASS_ID, TestDemoBuilder,
+        entities_state, string_tables_state,
+    };
+
+    use super::*;
+    use crate::BroadcastFile;
+    use crate::demostream::read_cmd_header;
+
+    fn packet(msgs: &[(SvcMessages, &[u8])]) -> Vec<u8> {
+        let mut packet_builder = PacketBuilder::new();
+        for (msg_type, data) in msgs {
+            packet_builder.push_msg(*msg_type as u32, data);
+        }
+        packet_builder.finish().encode_to_vec()
+    }
+
+    fn read_cmds(mut data: &[u8]) -> Result<Vec<(EDemoCommands, i32, Vec<u8>)>, anyhow::Error> {
+        let mut cmds = Vec::new();
+        while !data.is_empty() {
+            let cmd_header = read_cmd_header(&mut data)?;
+            let (body, rest) = data.split_at(cmd_header.body_size as usize);
+            cmds.push((cmd_header.cmd, cmd_header.tick, body.to_vec()));
+            data = rest;
+        }
+        Ok(cmds)
+    }
+
+    #[test]
+    fn test_respond() -> Result<(), anyhow::Error> {
+        let server_info = CsvcMsgServerInfo {
+            tick_interval: Some(1.0 / 30.0),
+            map_name: Some("dota".to_string()),
+            ..Default::default()
+        }
+        .encode_to_vec();
+
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        demo_writer.write_cmd(
+            EDemoCommands::DemSignonPacket,
+            -1,
+            &packet(&[(SvcMessages::SvcServerInfo, &server_info)]),
+            false,
+        )?;
+        demo_writer.write_cmd(EDemoCommands::DemSyncTick, -1, &[], false)?;
+        // 3 fragments, 2 packets each
+        for tick in (0..180).step_by(30) {
+            let entities = [tick as u8];
+            let body = packet(&[(SvcMessages::SvcPacketEntities, &entities)]);
+            demo_writer.write_cmd(EDemoCommands::DemPacket, tick, &body, true)?;
+        }
+        demo_writer.write_cmd(EDemoCommands::DemStop, 180, &[], false)?;
+        let data = demo_writer.finish()?.into_inner();
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let options = BroadcastServerOptions {
+            keyframe_interval: 2,
+            speed: f64::INFINITY,
+        };
+        let mut server = BroadcastServer::new(demo_file, options)?;
+        assert_eq!(server.num_fragments(), 3);
+
+        let response = server.respond("/tv/42/sync");
+        assert_eq!(response.status, 200);
+        let sync_response: SyncResponse = serde_json::from_slice(&response.body)?;
+        assert_eq!(sync_response.fragment, 2);
+        assert_eq!(sync_response.tick, 120);
+        assert_eq!(sync_response.tps, 30);
+        assert_eq!(sync_response.map, "dota");
+
+        let response = server.respond("/tv/42/0/start");
+        assert_eq!(response.status, 200);
+        let cmds = read_cmds(&response.body)?;
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].0, EDemoCommands::DemSignonPacket);
+
+        let response = server.respond("/tv/42/1/delta");
+        assert_eq!(response.status, 200);
+        let ticks: Vec<i32> = read_cmds(&response.body)?
+            .iter()
+            .map(|(_, tick, _)| *tick)
+            .collect();
+        assert_eq!(ticks, [60, 90]);
+
+        let response = server.respond("/tv/42/2/full");
+        assert_eq!(response.status, 200);
+        let cmds = read_cmds(&response.body)?;
+        assert_eq!(cmds.len(), 1);
+        assert_eq!((cmds[0].0, cmds[0].1), (EDemoCommands::DemPacket, 120));
+        let mut entities = Vec::new();
+        for_each_packet_msg(&cmds[0].2, |_msg_type, data| {
+            entities.extend_from_slice(data);
+            Ok(())
+        })?;
+        assert_eq!(entities, [0, 30, 60, 90]);
+
+        assert_eq!(server.respond("/tv/42/3/delta").status, 404);
+        assert_eq!(server.respond("/tv/42/1/start").status, 404);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_from_late_fragment() -> Result<(), anyhow::Error> {
+        // instancebaseline changes at tick 1810, but only within the full packet; entity 3 that is
+        // created later relies on it.
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(100))])
+            .create_entity(
+                1,
+                TEST_GAME_RULES_CLASS_ID,
+                1,
+                &[(ROUND, FieldValue::U64(1))],
+            )
+            .create_entity(2, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(10)?
+            .write_full_packet(10)?;
+        for tick in (40..2400).step_by(30) {
+            builder.update_entity(2, &[(HEALTH, FieldValue::I64(tick as i64))]);
+            match tick {
+                1810 => {
+                    builder
+                        .update_entity(1, &[(ROUND, FieldValue::U64(2))])
+                        .write_packet(tick)?
+                        .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(50))])
+                        .write_full_packet(tick)?;
+                    continue;
+                }
+                2110 => {
+                    builder.create_entity(3, TEST_ENTITY_CLASS_ID, 1, &[]);
+                }
+                _ => {}
+            }
+            builder.write_packet(tick)?;
+        }
+        let data = builder.finish(2400)?;
+
+        let demo_file = DemoFile::start_reading(Cursor::new(&data))?;
+        let options = BroadcastServerOptions {
+            keyframe_interval: 2,
+            speed: f64::INFINITY,
+        };
+        let mut server = BroadcastServer::new(demo_file, options)?;
+        // fragments start at ticks 10, 70, 130, ..; fragment 35 spans [2110, 2170).
+        let fragment = 35;
+
+        let mut broadcast = Vec::new();
+        for path in [
+            "/tv/42/0/start".to_string(),
+            format!("/tv/42/{fragment}/full"),
+            format!("/tv/42/{fragment}/delta"),
+        ] {
+            let response = server.respond(&path);
+            assert_eq!(response.status, 200, "{path}");
+            broadcast.extend_from_slice(&response.body);
+        }
+
+        let mut parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&data))?)?;
+        parser.run_to_tick(2140)?;
+        let mut broadcast_parser =
+            Parser::from_stream(BroadcastFile::start_reading(Cursor::new(broadcast)))?;
+        broadcast_parser.run_to_end()?;
+        assert_eq!(broadcast_parser.context().tick(), 2140);
+
+        let entities = entities_state(broadcast_parser.context());
+        assert_eq!(entities.len(), 3);
+        assert_eq!(entities, entities_state(parser.context()));
+
+        let health = broadcast_parser
+            .context()
+            .entities()
+            .and_then(|entities| entities.get(&3))
+            .and_then(|entity| entity.iter().next().map(|(_, value)| value.clone()));
+        assert!(matches!(health, Some(FieldValue::I64(50))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_from_string_table_snapshot() -> Result<(), anyhow::Error> {
+        // instancebaseline changes every 300 ticks, full packets are at 10 and 1810.
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(0))])
+            .create_entity(2, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(10)?
+            .write_full_packet(10)?;
+        for tick in (40..2400).step_by(30) {
+            builder.update_entity(2, &[(HEALTH, FieldValue::I64(tick as i64))]);
+            if tick % 300 == 100 {
+                builder.update_baseline(
+                    TEST_ENTITY_CLASS_ID,
+                    &[(HEALTH, FieldValue::I64(tick as i64))],
+                );
+            }
+            builder.write_packet(tick)?;
+            if tick == 1810 {
+                builder.write_full_packet(tick)?;
+            }
+        }
+        let data = builder.finish(2400)?;
+
+        let demo_file = DemoFile::start_reading(Cursor::new(&data))?;
+        let options = BroadcastServerOptions {
+            keyframe_interval: 2,
+            speed: f64::INFINITY,
+        };
+        let mut server = BroadcastServer::new(demo_file, options)?;
+        // fragment 35 starts at 2110
+        let full = server.respond("/tv/42/35/full").body;
+
+        // string table history that precedes the full packet is superseded by its snapshot;
+        // what remains is the snapshot itself (encoded) and the change at 2000.
+        let mut num_string_table_msgs = 0;
+        for (_, _, body) in read_cmds(&full)? {
+            for_each_packet_msg(&body, |msg_type, _| {
+                if msg_type == SvcMessages::SvcUpdateStringTable as u32 {
+                    num_string_table_msgs += 1;
+                }
+                Ok(())
+            })?;
+        }
+        assert_eq!(num_string_table_msgs, 2);
+
+        let broadcast = [server.respond("/tv/42/0/start").body, full].concat();
+        let mut broadcast_parser =
+            Parser::from_stream(BroadcastFile::start_reading(Cursor::new(broadcast)))?;
+        broadcast_parser.run_to_end()?;
+        assert_eq!(broadcast_parser.context().tick(), 2110);
+
+        // NOTE: full fragment contains state up to (not including) its start tick.
+        let mut parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&data))?)?;
+        parser.run_to_tick(2080)?;
+        assert_eq!(
+            entities_state(broadcast_parser.context()),
+            entities_state(parser.context())
+        );
+        assert_eq!(
+            string_tables_state(broadcast_parser.context()),
+            string_tables_state(parser.context())
+        );
+
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
-use std::io::{Read, SeekFrom};
+use std::io::{self, Read, Write};
 
-use haste_core::demostream::{CmdHeader, DecodeCmdError, DemoStream, ReadCmdHeaderError};
+use haste_core::demostream::{CmdHeader, DecodeCmdError, ReadCmdHeaderError};
 use prost::Message;
 use valveprotos::common::{
     CDemoClassInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables, EDemoCommands,
@@ -56,6 +56,21 @@ pub(crate) fn read_cmd_header<R: Read>(mut rdr: R) -> Result<CmdHeader, ReadCmdH
     })
 }
 
+#[inline]
+pub(crate) fn write_cmd_header<W: Write>(
+    mut wtr: W,
+    cmd: EDemoCommands,
+    tick: i32,
+    body_size: u32,
+) -> Result<(), io::Error> {
+    wtr.write_all(&[cmd as u8])?;
+    wtr.write_all(&(tick as u32).to_le_bytes())?;
+    // NOTE: meaning of this byte is unknown; it is being ignored by read_cmd_header.
+    wtr.write_all(&[0])?;
+    wtr.write_all(&body_size.to_le_bytes())?;
+    Ok(())
+}
+
 // cmd
 // ----
 
@@ -68,6 +83,16 @@ pub(crate) fn decode_cmd_send_tables(data: &[u8]) -> Result<CDemoSendTables, Dec
     })
 }
 
+// NOTE: this is an inverse of decode_cmd_send_tables. meaning of the 4 bytes that precede the data
+// is unknown (decoder ignores them); size of the data is being written there.
+pub(crate) fn encode_cmd_send_tables(cmd: &CDemoSendTables) -> Vec<u8> {
+    let data = cmd.data();
+    let mut buf = Vec::with_capacity(size_of::<u32>() + data.len());
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+    buf
+}
+
 #[inline(always)]
 pub(crate) fn decode_cmd_class_info(data: &[u8]) -> Result<CDemoClassInfo, DecodeCmdError> {
     CDemoClassInfo::decode(data).map_err(DecodeCmdError::DecodeProtobufError)
@@ -87,24 +112,3 @@ pub(crate) fn decode_cmd_full_packet(_data: &[u8]) -> Result<CDemoFullPacket, De
     // NOTE: broadcasts don't seem to contain full packets
     unreachable!()
 }
-
-// other
-// ----
-
-pub(crate) fn scan_for_last_tick(demo_stream: &mut impl DemoStream) -> Result<i32, anyhow::Error> {
-    let mut last_tick: i32 = -1;
-    let backup = demo_stream.stream_position()?;
-    loop {
-        match demo_stream.read_cmd_header() {
-            Ok(cmd_header) => {
-                last_tick = cmd_header.tick;
-                demo_stream.skip_cmd(&cmd_header)?;
-            }
-            Err(_) if demo_stream.is_at_eof().unwrap_or_default() => {
-                demo_stream.seek(SeekFrom::Start(backup))?;
-                return Ok(last_tick);
-            }
-            Err(err) => return Err(err.into()),
-        }
-    }
-}
@@ -1,8 +1,29 @@
+mod boundedbuffer;
+mod broadcastconverter;
 mod broadcastfile;
 mod broadcasthttp;
+mod broadcastserver;
 pub(crate) mod demostream;
 mod httpclient;
+#[cfg(any(test, feature = "test-util"))]
+mod recordedhttpclient;
+mod sleep;
+mod streambuffer;
 
+pub use boundedbuffer::{BoundedBuffer, BoundedBufferOptions};
+pub use broadcastconverter::BroadcastConverter;
 pub use broadcastfile::BroadcastFile;
-pub use broadcasthttp::{default_headers, BroadcastHttp, BroadcastHttpClientError};
+pub use broadcasthttp::{
+    default_headers, BroadcastHttp, BroadcastHttpClientError, RetryPolicy, SyncResponse,
+};
+pub use broadcastserver::{BroadcastServer, BroadcastServerOptions, BroadcastServerResponse};
 pub use httpclient::HttpClient;
+pub use pollster::block_on;
+#[cfg(any(test, feature = "test-util"))]
+pub use recordedhttpclient::{RecordedHttpClient, RecordedHttpClientError, RecordedResponse};
+#[cfg(feature = "tokio")]
+pub use sleep::TokioSleep;
+pub use sleep::{DefaultSleep, Sleep, ThreadSleep};
+pub use streambuffer::{
+    LastPacketBuffer, PacketType, SeekableBuffer, SeekableStreamBuffer, StreamBuffer,
+};
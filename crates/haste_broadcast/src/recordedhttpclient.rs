@@ -0,0 +1,255 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::{fs, io};
+
+use bytes::Bytes;
+
+use crate::httpclient::HttpClient;
+
+// NOTE: requests are matched by last segments of the path (the part that goes after broadcast's
+// base url), that is:
+// - `sync`
+// - `<fragment>/start`
+// - `<fragment>/full`
+// - `<fragment>/delta`
+//
+// requests that don't have a recorded response get 404 (this is what valve's relay does for
+// fragments that are not available (yet)).
+
+#[derive(Debug, Clone)]
+pub enum RecordedResponse {
+    Ok(Bytes),
+    Status(http::StatusCode),
+    /// request fails with [`RecordedHttpClientError::Timeout`].
+    Timeout,
+    /// response is successful, but the body is [`RecordedHttpClientError::BadGzip`]; see notes in
+    /// httpclient.rs.
+    BadGzip,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RecordedHttpClientError {
+    #[error("request timed out")]
+    Timeout,
+    #[error("content-encoding is gzip, but the body is not gzip-encoded")]
+    BadGzip,
+}
+
+/// [`HttpClient`] that replays recorded responses. meant for deterministic tests.
+///
+/// each request key (see [`RecordedHttpClient::push_response`]) has a queue of responses; each
+/// request takes the next one, the last one is being repeated.
+#[derive(Default)]
+pub struct RecordedHttpClient {
+    responses: Mutex<HashMap<String, VecDeque<RecordedResponse>>>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl RecordedHttpClient {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// loads responses from the directory that mirrors broadcast's url structure, i.e.:
+    /// - `<dir>/sync`
+    /// - `<dir>/0/start`
+    /// - `<dir>/295/full`
+    /// - `<dir>/296/delta`
+    ///
+    /// file names can have an attempt number and an outcome suffix: `<name>[~<attempt>][.<outcome>]`
+    /// where outcome is either a status code (e.g. `404`), `timeout` or `badgzip`. for example
+    /// `296/delta~0.404` followed by `296/delta~1` means that first request will result in 404
+    /// and the second in 200 with contents of the file as body. attempt number defaults to 0.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let mut recorded: Vec<(String, u32, RecordedResponse)> = Vec::new();
+        load_dir(dir.as_ref(), "", &mut recorded)?;
+        recorded.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+        let this = Self::new();
+        for (key, _attempt, response) in recorded {
+            this.push_response(key, response);
+        }
+        Ok(this)
+    }
+
+    /// appends a response to the queue of the given key (for example `sync` or `296/delta`).
+    pub fn push_response(&self, key: impl Into<String>, response: RecordedResponse) {
+        self.responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(key.into())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// returns keys of all requests that were made so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn next_response(&self, key: &str) -> RecordedResponse {
+        let mut responses = self
+            .responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match responses.get_mut(key) {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        }
+        .unwrap_or(RecordedResponse::Status(http::StatusCode::NOT_FOUND))
+    }
+}
+
+fn load_dir(
+    dir: &Path,
+    prefix: &str,
+    recorded: &mut Vec<(String, u32, RecordedResponse)>,
+) -> Result<(), io::Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        if entry.file_type()?.is_dir() {
+            load_dir(&entry.path(), &format!("{prefix}{file_name}/"), recorded)?;
+            continue;
+        }
+
+        let (name, outcome) = match file_name.split_once('.') {
+            Some((name, outcome)) => (name, Some(outcome)),
+            None => (file_name.as_ref(), None),
+        };
+        let (name, attempt) = match name.split_once('~') {
+            Some((name, attempt)) => (
+                name,
+                attempt
+                    .parse::<u32>()
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            ),
+            None => (name, 0),
+        };
+
+        let response = match outcome {
+            None => RecordedResponse::Ok(fs::read(entry.path())?.into()),
+            Some("timeout") => RecordedResponse::Timeout,
+            Some("badgzip") => RecordedResponse::BadGzip,
+            Some(status) => RecordedResponse::Status(
+                status
+                    .parse::<u16>()
+                    .ok()
+                    .and_then(|status| http::StatusCode::from_u16(status).ok())
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid outcome in {file_name}"),
+                        )
+                    })?,
+            ),
+        };
+
+        recorded.push((format!("{prefix}{name}"), attempt, response));
+    }
+    Ok(())
+}
+
+fn request_key(uri: &http::Uri) -> String {
+    let mut segments = uri.path().trim_end_matches('/').rsplit('/');
+    match (segments.next(), segments.next()) {
+        (Some("sync"), _) | (Some(_), None) => "sync".to_string(),
+        (Some(last), Some(second_last)) => format!("{second_last}/{last}"),
+        (None, _) => String::new(),
+    }
+}
+
+impl HttpClient for RecordedHttpClient {
+    type Error = RecordedHttpClientError;
+
+    async fn execute(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Result<Bytes, Self::Error>>, Self::Error> {
+        let key = request_key(request.uri());
+        let response = self.next_response(&key);
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(key);
+
+        let (status, body) = match response {
+            RecordedResponse::Ok(body) => (http::StatusCode::OK, Ok(body)),
+            RecordedResponse::Status(status) => (status, Ok(Bytes::new())),
+            RecordedResponse::Timeout => return Err(RecordedHttpClientError::Timeout),
+            RecordedResponse::BadGzip => {
+                (http::StatusCode::OK, Err(RecordedHttpClientError::BadGzip))
+            }
+        };
+
+        let mut response = http::Response::new(body);
+        *response.status_mut() = status;
+        Ok(response)
+    }
+}
+
+// NOTE: this allows to hand a reference over to BroadcastHttp and to inspect requests afterwards.
+impl HttpClient for &RecordedHttpClient {
+    type Error = RecordedHttpClientError;
+
+    #[inline]
+    async fn execute(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Result<Bytes, Self::Error>>, Self::Error> {
+        (*self).execute(request).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_dir() -> Result<(), io::Error> {
+        let dir = std::env::temp_dir().join(format!("haste-recorded-{}", std::process::id()));
+        fs::create_dir_all(dir.join("0"))?;
+        fs::create_dir_all(dir.join("1"))?;
+        fs::write(dir.join("sync"), b"{}")?;
+        fs::write(dir.join("0/start"), b"start")?;
+        fs::write(dir.join("1/delta~0.404"), b"")?;
+        fs::write(dir.join("1/delta~1.timeout"), b"")?;
+        fs::write(dir.join("1/delta~2"), b"delta")?;
+        let client = RecordedHttpClient::from_dir(&dir);
+        fs::remove_dir_all(&dir)?;
+        let client = client?;
+
+        assert!(
+            matches!(client.next_response("0/start"), RecordedResponse::Ok(body) if body == "start")
+        );
+        assert!(matches!(
+            client.next_response("1/delta"),
+            RecordedResponse::Status(http::StatusCode::NOT_FOUND)
+        ));
+        assert!(matches!(
+            client.next_response("1/delta"),
+            RecordedResponse::Timeout
+        ));
+        assert!(
+            matches!(client.next_response("1/delta"), RecordedResponse::Ok(body) if body == "delta")
+        );
+        // last one repeats
+        assert!(
+            matches!(client.next_response("1/delta"), RecordedResponse::Ok(body) if body == "delta")
+        );
+        assert!(matches!(
+            client.next_response("2/delta"),
+            RecordedResponse::Status(http::StatusCode::NOT_FOUND)
+        ));
+
+        Ok(())
+    }
+}
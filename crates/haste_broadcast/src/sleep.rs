@@ -0,0 +1,69 @@
+use std::future::Future;
+use std::time::Duration;
+
+// NOTE: BroadcastHttp needs to wait for fragments to become available; there's no executor-agnostic
+// way to sleep in async context, thus the trait.
+
+/// timer that [`crate::BroadcastHttp`] uses to wait for fragments. implement it to plug in timers
+/// of your executor.
+pub trait Sleep {
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()>;
+
+    /// resolves to `None` if `future` did not complete within `duration`.
+    ///
+    /// default implementation does not enforce the limit (it can't be done without help of an
+    /// executor); http client is expected to have its own timeouts.
+    fn timeout<F: Future>(
+        &self,
+        duration: Duration,
+        future: F,
+    ) -> impl Future<Output = Option<F::Output>> {
+        let _ = duration;
+        async { Some(future.await) }
+    }
+}
+
+/// sleeps with [`std::thread::sleep`], which blocks the thread. meant to be used with
+/// [`crate::BroadcastHttp::next_packet_blocking`] (/ [`pollster::block_on`]), not within async
+/// executors.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ThreadSleep;
+
+impl Sleep for ThreadSleep {
+    async fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use std::future::Future;
+    use std::time::Duration;
+
+    use super::Sleep;
+
+    /// sleeps with [`tokio::time::sleep`]; requires tokio runtime with time enabled.
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct TokioSleep;
+
+    impl Sleep for TokioSleep {
+        async fn sleep(&self, duration: Duration) {
+            tokio::time::sleep(duration).await;
+        }
+
+        async fn timeout<F: Future>(&self, duration: Duration, future: F) -> Option<F::Output> {
+            tokio::time::timeout(duration, future).await.ok()
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use tokio_impl::TokioSleep;
+
+/// sleep that [`crate::BroadcastHttp`] uses unless told otherwise (see
+/// [`crate::BroadcastHttp::with_sleep`]); [`TokioSleep`] when tokio feature is enabled,
+/// [`ThreadSleep`] otherwise.
+#[cfg(feature = "tokio")]
+pub type DefaultSleep = TokioSleep;
+#[cfg(not(feature = "tokio"))]
+pub type DefaultSleep = ThreadSleep;
@@ -0,0 +1,200 @@
+use std::io::{self, BufRead, Cursor, Seek, SeekFrom};
+
+use bytes::buf::Reader;
+use bytes::{Buf, Bytes};
+use haste_core::demostream::{CmdHeader, ReadCmdError, ReadCmdHeaderError};
+
+use crate::demostream::read_cmd_header;
+
+// NOTE: buffer is a type parameter of BroadcastHttp (instead of being an enum) so that
+// BroadcastHttp could implement SeekableDemoStream only when the buffer allows seeking.
+
+/// what [`crate::BroadcastHttp::next_packet`] received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketType {
+    Start,
+    Full,
+    Delta,
+}
+
+/// where [`crate::BroadcastHttp`] keeps packets that were received and reads cmds from.
+pub trait StreamBuffer {
+    fn push(&mut self, packet_type: PacketType, packet: &Bytes);
+
+    /// whether an extra full fragment needs to be requested (and passed to
+    /// [`StreamBuffer::splice_full`]) before pushing the next delta fragment.
+    #[inline]
+    fn needs_full(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn splice_full(&mut self, full: Bytes) {
+        let _ = full;
+    }
+
+    fn is_at_eof(&self) -> bool;
+
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError>;
+
+    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError>;
+
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error>;
+}
+
+/// buffers that retain data and allow seeking within it.
+pub trait SeekableStreamBuffer: StreamBuffer {
+    fn cursor(&self) -> &Cursor<Vec<u8>>;
+
+    fn cursor_mut(&mut self) -> &mut Cursor<Vec<u8>>;
+}
+
+// ----
+// last packet
+
+/// keeps only the last packet. this is the default.
+#[derive(Default)]
+pub struct LastPacketBuffer(Option<Reader<Bytes>>);
+
+impl StreamBuffer for LastPacketBuffer {
+    fn push(&mut self, _packet_type: PacketType, packet: &Bytes) {
+        // NOTE: clone is not cloning underlying bytes, but just increases ref count.
+        self.0 = Some(packet.clone().reader());
+    }
+
+    /// true if `next_packet` never succeeded.
+    fn is_at_eof(&self) -> bool {
+        self.0.as_ref().is_none_or(|r| !r.get_ref().has_remaining())
+    }
+
+    /// fails with [`io::ErrorKind::UnexpectedEof`] if `next_packet` never succeeded.
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
+        match self.0 {
+            Some(ref mut r) => read_cmd_header(r),
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+
+    /// fails with [`io::ErrorKind::UnexpectedEof`] if `next_packet` never succeeded.
+    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
+        let Some(ref mut r) = self.0 else {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        };
+
+        let size = cmd_header.body_size as usize;
+        let bytes = r.get_mut();
+
+        // it probably could be possible that body of the response was not transferred /
+        // read correctly?
+        if bytes.remaining() < size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        // SAFETY: this is safe because lifetime of the returned slice is tied to the
+        // lifetime of r (if i'm not missing anything, am i?).
+        let data = unsafe {
+            // NOTE: start is 0 because Reader's advance will increase start position of
+            // the underlying slice
+            let ptr = bytes.as_ref()[0..size].as_ptr();
+            std::slice::from_raw_parts(ptr, size)
+        };
+        bytes.advance(size);
+        Ok(data)
+    }
+
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        let Some(ref mut r) = self.0 else {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        };
+
+        let size = cmd_header.body_size as usize;
+        let bytes = r.get_mut();
+        if bytes.remaining() < size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        bytes.advance(size);
+        Ok(())
+    }
+}
+
+// ----
+// seekable
+
+/// keeps everything. this enables seeking.
+#[derive(Default)]
+pub struct SeekableBuffer(Cursor<Vec<u8>>);
+
+impl StreamBuffer for SeekableBuffer {
+    fn push(&mut self, _packet_type: PacketType, packet: &Bytes) {
+        // NOTE: appending must not move the read position.
+        self.0.get_mut().extend_from_slice(packet);
+    }
+
+    #[inline]
+    fn is_at_eof(&self) -> bool {
+        cursor_is_at_eof(&self.0)
+    }
+
+    #[inline]
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
+        read_cmd_header(&mut self.0)
+    }
+
+    #[inline]
+    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
+        cursor_read_cmd(&mut self.0, cmd_header)
+    }
+
+    #[inline]
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        cursor_skip_cmd(&mut self.0, cmd_header)
+    }
+}
+
+impl SeekableStreamBuffer for SeekableBuffer {
+    #[inline]
+    fn cursor(&self) -> &Cursor<Vec<u8>> {
+        &self.0
+    }
+
+    #[inline]
+    fn cursor_mut(&mut self) -> &mut Cursor<Vec<u8>> {
+        &mut self.0
+    }
+}
+
+// ----
+// cursor helpers (for seekable buffers)
+
+#[inline]
+pub(crate) fn cursor_is_at_eof(c: &Cursor<Vec<u8>>) -> bool {
+    c.position() as usize >= c.get_ref().len()
+}
+
+pub(crate) fn cursor_read_cmd<'a>(
+    c: &'a mut Cursor<Vec<u8>>,
+    cmd_header: &CmdHeader,
+) -> Result<&'a [u8], ReadCmdError> {
+    let size = cmd_header.body_size as usize;
+    let pos = c.position() as usize;
+
+    // it probably could be possible that body of the response was not transferred /
+    // read correctly?
+    let remaining = c.get_ref().len().saturating_sub(pos);
+    if remaining < size {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    // NOTE: Cursor's advance will not discard data from the underlying Vec<u8>
+    c.consume(size);
+    Ok(&c.get_ref()[pos..pos + size])
+}
+
+#[inline]
+pub(crate) fn cursor_skip_cmd(
+    c: &mut Cursor<Vec<u8>>,
+    cmd_header: &CmdHeader,
+) -> Result<(), io::Error> {
+    c.seek(SeekFrom::Current(cmd_header.body_size as i64))
+        .map(|_| ())
+}
@@ -0,0 +1,197 @@
+// BitWriter is an inverse of BitReader (/ a port of valve's CBitWrite or/and old_bf_write from
+// valve's tier1 lib). bits are written in the same order in which BitReader reads them - least
+// significant first.
+//
+// NOTE: this is not being used on hot paths (the parser never writes anything), bits are written
+// one by one when the stream is not byte aligned.
+#[derive(Default)]
+pub struct BitWriter {
+    buf: Vec<u8>,
+    num_bits: usize,
+}
+
+impl BitWriter {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn num_bits_written(&self) -> usize {
+        self.num_bits
+    }
+
+    #[inline]
+    fn is_byte_aligned(&self) -> bool {
+        self.num_bits & 7 == 0
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        if self.is_byte_aligned() {
+            self.buf.push(0);
+        }
+        if value {
+            // NOTE: buf can't be empty, if it was - a byte was pushed above.
+            let last = self.buf.len() - 1;
+            self.buf[last] |= 1 << (self.num_bits & 7);
+        }
+        self.num_bits += 1;
+    }
+
+    pub fn write_ubit64(&mut self, value: u64, num_bits: usize) {
+        debug_assert!(num_bits <= 64);
+        for i in 0..num_bits {
+            self.write_bool((value >> i) & 1 == 1);
+        }
+    }
+
+    #[inline]
+    pub fn write_byte(&mut self, value: u8) {
+        if self.is_byte_aligned() {
+            self.buf.push(value);
+            self.num_bits += 8;
+        } else {
+            self.write_ubit64(value as u64, 8);
+        }
+    }
+
+    pub fn write_bytes(&mut self, buf: &[u8]) {
+        if self.is_byte_aligned() {
+            self.buf.extend_from_slice(buf);
+            self.num_bits += buf.len() * 8;
+        } else {
+            buf.iter().for_each(|&byte| self.write_byte(byte));
+        }
+    }
+
+    pub fn write_uvarint32(&mut self, mut value: u32) {
+        while value >= 0x80 {
+            self.write_byte((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.write_byte(value as u8);
+    }
+
+    pub fn write_uvarint64(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.write_byte((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.write_byte(value as u8);
+    }
+
+    /// zigzag encoded; inverse of [`crate::bitreader::BitReader::read_varint32`].
+    #[inline]
+    pub fn write_varint32(&mut self, value: i32) {
+        self.write_uvarint32(((value << 1) ^ (value >> 31)) as u32);
+    }
+
+    /// zigzag encoded; inverse of [`crate::bitreader::BitReader::read_varint64`].
+    #[inline]
+    pub fn write_varint64(&mut self, value: i64) {
+        self.write_uvarint64(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    /// inverse of [`crate::bitreader::BitReader::read_ubitvar`].
+    pub fn write_ubitvar(&mut self, value: u32) {
+        let value = value as u64;
+        // NOTE: lower 4 bits are the value, next 2 bits specify how many more bits follow.
+        if value < 1 << 4 {
+            self.write_ubit64(value, 6);
+        } else if value < 1 << (4 + 4) {
+            self.write_ubit64((value & 15) | 16, 6);
+            self.write_ubit64(value >> 4, 4);
+        } else if value < 1 << (4 + 8) {
+            self.write_ubit64((value & 15) | 32, 6);
+            self.write_ubit64(value >> 4, 8);
+        } else {
+            self.write_ubit64((value & 15) | 48, 6);
+            self.write_ubit64(value >> 4, 32 - 4);
+        }
+    }
+
+    /// inverse of [`crate::bitreader::BitReader::read_ubitvarfp`].
+    pub fn write_ubitvarfp(&mut self, value: u32) {
+        let value = value as u64;
+        // NOTE: each bool tells whether value fits into the next size class.
+        for num_bits in [2, 4, 10, 17] {
+            if value < 1 << num_bits {
+                self.write_bool(true);
+                self.write_ubit64(value, num_bits);
+                return;
+            }
+            self.write_bool(false);
+        }
+        self.write_ubit64(value, 31);
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// returns written data. trailing bits of the last byte (if the stream is not byte aligned)
+    /// are zeros.
+    #[inline]
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitreader::{BitReader, BitReaderOverflowError};
+
+    #[test]
+    fn test_write_and_read() -> Result<(), BitReaderOverflowError> {
+        let ubitvars = [0, 15, 16, 255, 256, 4095, 4096, u32::MAX >> 4];
+        let uvarints = [0, 127, 128, 16383, 16384, u32::MAX];
+        let varints = [0, -1, 1, -64, 64, i64::MIN, i64::MAX];
+        let ubitvarfps = [0, 3, 4, 15, 16, 1023, 1024, 131071, 131072, u32::MAX >> 1];
+
+        let mut bw = BitWriter::new();
+        bw.write_bool(true);
+        bw.write_ubit64(0b101, 3);
+        for value in ubitvars {
+            bw.write_ubitvar(value);
+        }
+        for value in uvarints {
+            bw.write_uvarint32(value);
+            bw.write_uvarint64(value as u64 * 3);
+        }
+        for value in varints {
+            bw.write_varint32(value as i32);
+            bw.write_varint64(value);
+        }
+        for value in ubitvarfps {
+            bw.write_ubitvarfp(value);
+        }
+        bw.write_bytes(b"hello");
+        let num_bits = bw.num_bits_written();
+        let buf = bw.into_inner();
+        assert_eq!(buf.len(), num_bits.div_ceil(8));
+
+        let mut br = BitReader::new(&buf);
+        assert!(br.read_bool());
+        assert_eq!(br.read_ubit64(3), 0b101);
+        for value in ubitvars {
+            assert_eq!(br.read_ubitvar(), value);
+        }
+        for value in uvarints {
+            assert_eq!(br.read_uvarint32(), value);
+            assert_eq!(br.read_uvarint64(), value as u64 * 3);
+        }
+        for value in varints {
+            assert_eq!(br.read_varint32(), value as i32);
+            assert_eq!(br.read_varint64(), value);
+        }
+        for value in ubitvarfps {
+            assert_eq!(br.read_ubitvarfp(), value);
+        }
+        let mut hello = [0u8; 5];
+        br.read_bytes(&mut hello);
+        assert_eq!(&hello, b"hello");
+        br.is_overflowed()
+    }
+}
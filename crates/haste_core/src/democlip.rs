@@ -0,0 +1,478 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use valveprotos::common::{
+    CDemoFullPacket, CDemoPacket, CDemoSpawnGroups, CsvcMsgCreateStringTable,
+    CsvcMsgUpdateStringTable, EDemoCommands, SvcMessages,
+};
+use valveprotos::prost::Message;
+
+use crate::bitreader::BitReader;
+use crate::bitwriter::BitWriter;
+use crate::demofile::DemoFile;
+use crate::demostream::{DemoStream, SeekableDemoStream};
+use crate::demowriter::DemoWriter;
+use crate::stringtables::StringTableContainer;
+
+// NOTE: there's no entity encoder, thus state at the start tick can't be "serialized" out of the
+// parser. instead messages that carry state are being collected and concatenated into a single
+// packet; replaying it produces the same state as parsing the original replay up to the start
+// tick. string tables are an exception, they can be encoded (see StateSnapshot).
+
+/// iterates over messages within packet data (see [`CDemoPacket`]).
+pub fn for_each_packet_msg<F>(data: &[u8], mut f: F) -> Result<(), anyhow::Error>
+where
+    F: FnMut(u32, &[u8]) -> Result<(), anyhow::Error>,
+{
+    let mut br = BitReader::new(data);
+    let mut buf = Vec::new();
+    let result = (|| {
+        while br.num_bits_left() > 8 {
+            let msg_type = br.read_ubitvar();
+            let size = br.read_uvarint32() as usize;
+            // NOTE: reads are not bounds checked (see BitReader); corrupt size must not result in
+            // an attempt to read (/ allocate) garbage amount of memory.
+            if size * 8 > br.num_bits_left() {
+                anyhow::bail!("packet msg size ({size}) is out of bounds");
+            }
+            buf.resize(size, 0);
+            br.read_bytes(&mut buf);
+            f(msg_type, &buf)?;
+        }
+        Ok(())
+    })();
+    // NOTE: overflow must be checked in any case, BitReader panics on drop otherwise.
+    br.is_overflowed()?;
+    result
+}
+
+/// assembles packet data (see [`CDemoPacket`]) out of messages.
+#[derive(Default)]
+pub struct PacketBuilder {
+    bw: BitWriter,
+}
+
+impl PacketBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_msg(&mut self, msg_type: u32, data: &[u8]) {
+        self.bw.write_ubitvar(msg_type);
+        self.bw.write_uvarint32(data.len() as u32);
+        self.bw.write_bytes(data);
+    }
+
+    #[inline]
+    pub fn finish(self) -> CDemoPacket {
+        CDemoPacket {
+            data: Some(self.bw.into_inner()),
+        }
+    }
+}
+
+fn is_string_table_msg(msg_type: u32) -> bool {
+    msg_type == SvcMessages::SvcCreateStringTable as u32
+        || msg_type == SvcMessages::SvcUpdateStringTable as u32
+}
+
+fn is_state_msg(msg_type: u32) -> bool {
+    is_string_table_msg(msg_type) || msg_type == SvcMessages::SvcPacketEntities as u32
+}
+
+/// collects messages that carry state (string tables and entities) from packets that come after
+/// signon (after [`EDemoCommands::DemSyncTick`]) and can synthesize a single packet that
+/// reproduces the state.
+///
+/// messages that do not carry state (user messages, game events, etc.) are dropped.
+#[derive(Default)]
+pub struct StateSnapshot {
+    /// string tables as of the last full packet. string table messages that came before it are
+    /// superseded by full packet's snapshot of string tables (CDemoStringTables); there's no way
+    /// to express the snapshot as a message within a packet, thus tables are being encoded back
+    /// into messages (see [`StateSnapshot::to_packet`]).
+    string_tables: StringTableContainer,
+    /// number of string tables that were created within signon; they do not need to be
+    /// re-created.
+    num_signon_string_tables: usize,
+    /// state messages of the last full packet's packet (entity snapshot).
+    full_packet_msgs: Vec<(u32, Vec<u8>)>,
+    /// state messages that came after the last full packet.
+    delta_msgs: Vec<(u32, Vec<u8>)>,
+}
+
+fn handle_string_table_msg(
+    string_tables: &mut StringTableContainer,
+    msg_type: u32,
+    data: &[u8],
+) -> Result<(), anyhow::Error> {
+    if msg_type == SvcMessages::SvcCreateStringTable as u32 {
+        string_tables.handle_create_msg(&CsvcMsgCreateStringTable::decode(data)?)?;
+    } else if msg_type == SvcMessages::SvcUpdateStringTable as u32 {
+        string_tables.handle_update_msg(&CsvcMsgUpdateStringTable::decode(data)?)?;
+    }
+    Ok(())
+}
+
+impl StateSnapshot {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// handles [`EDemoCommands::DemSignonPacket`] (/ [`EDemoCommands::DemPacket`]) that comes
+    /// before [`EDemoCommands::DemSyncTick`]. signon is not a part of the snapshot, but string
+    /// tables that are created within it are needed to be able to encode string tables back
+    /// into messages.
+    pub fn handle_signon_packet(&mut self, packet: &CDemoPacket) -> Result<(), anyhow::Error> {
+        for_each_packet_msg(packet.data(), |msg_type, data| {
+            handle_string_table_msg(&mut self.string_tables, msg_type, data)
+        })?;
+        self.num_signon_string_tables = self.string_tables.tables().count();
+        Ok(())
+    }
+
+    /// handles [`EDemoCommands::DemPacket`] (/ [`EDemoCommands::DemSignonPacket`]).
+    pub fn handle_packet(&mut self, packet: &CDemoPacket) -> Result<(), anyhow::Error> {
+        for_each_packet_msg(packet.data(), |msg_type, data| {
+            if is_state_msg(msg_type) {
+                self.delta_msgs.push((msg_type, data.to_vec()));
+            }
+            Ok(())
+        })
+    }
+
+    /// handles [`EDemoCommands::DemFullPacket`]. previously collected messages are discarded
+    /// because full packet contains entity snapshot and string table snapshot.
+    ///
+    /// full packets without a packet are ignored; collected messages are still needed to
+    /// reproduce entities.
+    pub fn handle_full_packet(
+        &mut self,
+        full_packet: &CDemoFullPacket,
+    ) -> Result<(), anyhow::Error> {
+        let Some(packet) = full_packet.packet.as_ref() else {
+            return Ok(());
+        };
+
+        for (msg_type, data) in self
+            .full_packet_msgs
+            .drain(..)
+            .chain(self.delta_msgs.drain(..))
+        {
+            handle_string_table_msg(&mut self.string_tables, msg_type, &data)?;
+        }
+        if let Some(string_tables) = full_packet.string_table.as_ref() {
+            self.string_tables.do_full_update(string_tables.clone());
+        }
+
+        for_each_packet_msg(packet.data(), |msg_type, data| {
+            if is_state_msg(msg_type) {
+                self.full_packet_msgs.push((msg_type, data.to_vec()));
+            }
+            Ok(())
+        })
+    }
+
+    /// synthesizes a packet that contains string tables as of the last full packet followed by
+    /// all collected state messages in order.
+    pub fn to_packet(&self) -> CDemoPacket {
+        let mut packet_builder = PacketBuilder::new();
+        for (table_id, string_table) in self.string_tables.tables().enumerate() {
+            if table_id < self.num_signon_string_tables {
+                if string_table.items().next().is_some() {
+                    packet_builder.push_msg(
+                        SvcMessages::SvcUpdateStringTable as u32,
+                        &string_table.to_update_msg(table_id as i32).encode_to_vec(),
+                    );
+                }
+            } else {
+                packet_builder.push_msg(
+                    SvcMessages::SvcCreateStringTable as u32,
+                    &string_table.to_create_msg().encode_to_vec(),
+                );
+            }
+        }
+        self.full_packet_msgs
+            .iter()
+            .chain(self.delta_msgs.iter())
+            .for_each(|(msg_type, data)| packet_builder.push_msg(*msg_type, data));
+        packet_builder.finish()
+    }
+
+    /// synthesizes a full packet; it carries string table snapshot as of the last full packet
+    /// and the same packet as [`StateSnapshot::to_packet`].
+    pub fn to_full_packet(&self) -> CDemoFullPacket {
+        CDemoFullPacket {
+            string_table: Some(self.string_tables.to_snapshot()),
+            packet: Some(self.to_packet()),
+        }
+    }
+}
+
+/// clips the replay to `[start_tick, end_tick]`. the result is a standalone replay that consists
+/// of:
+/// - signon cmds (everything up to and including first [`EDemoCommands::DemSyncTick`]);
+/// - synthesized [`EDemoCommands::DemPacket`] (and an equivalent [`EDemoCommands::DemFullPacket`]
+///   that also carries string table snapshot) at `start_tick` that contains state at `start_tick`
+///   (see [`StateSnapshot`]);
+/// - cmds within `(start_tick, end_tick]`;
+/// - [`EDemoCommands::DemStop`], spawn groups and file info.
+///
+/// ticks are not being shifted, the clip starts at `start_tick`.
+pub fn clip<R: Read + Seek, W: Write + Seek>(
+    demo_file: &mut DemoFile<R>,
+    wtr: W,
+    start_tick: i32,
+    end_tick: i32,
+) -> Result<W, anyhow::Error> {
+    if start_tick > end_tick {
+        anyhow::bail!("invalid tick range (start {start_tick}; end {end_tick})");
+    }
+
+    let mut demo_writer = DemoWriter::start_writing(wtr)?;
+    demo_file.seek(SeekFrom::Start(demo_file.start_position()))?;
+
+    // signon
+    let mut state_snapshot = StateSnapshot::new();
+    loop {
+        let cmd_header = demo_file.read_cmd_header()?;
+        let cmd_body = demo_file.read_cmd(&cmd_header)?;
+        demo_writer.write_cmd_with_header(&cmd_header, cmd_body)?;
+        match cmd_header.cmd {
+            EDemoCommands::DemSignonPacket | EDemoCommands::DemPacket => {
+                state_snapshot
+                    .handle_signon_packet(&DemoFile::<R>::decode_cmd_packet(cmd_body)?)?;
+            }
+            EDemoCommands::DemSyncTick => break,
+            _ => {}
+        }
+    }
+
+    // state at start tick
+    loop {
+        let cmd_header = demo_file.read_cmd_header()?;
+        if cmd_header.tick > start_tick {
+            demo_file.unread_cmd_header(&cmd_header)?;
+            break;
+        }
+        match cmd_header.cmd {
+            EDemoCommands::DemPacket | EDemoCommands::DemSignonPacket => {
+                let cmd = DemoFile::<R>::decode_cmd_packet(demo_file.read_cmd(&cmd_header)?)?;
+                state_snapshot.handle_packet(&cmd)?;
+            }
+            EDemoCommands::DemFullPacket => {
+                let cmd = DemoFile::<R>::decode_cmd_full_packet(demo_file.read_cmd(&cmd_header)?)?;
+                state_snapshot.handle_full_packet(&cmd)?;
+            }
+            EDemoCommands::DemStop => {
+                anyhow::bail!("start tick {start_tick} is past the end of the replay");
+            }
+            _ => demo_file.skip_cmd(&cmd_header)?,
+        }
+    }
+
+    // NOTE: Parser's run_to_end ignores full packets and run_to_tick needs a full packet to begin
+    // with; thus both.
+    demo_writer.write_cmd(
+        EDemoCommands::DemPacket,
+        start_tick,
+        &state_snapshot.to_packet().encode_to_vec(),
+        true,
+    )?;
+    let full_packet = state_snapshot.to_full_packet();
+    demo_writer.write_cmd(
+        EDemoCommands::DemFullPacket,
+        start_tick,
+        &full_packet.encode_to_vec(),
+        true,
+    )?;
+
+    // cmds within the range
+    let mut last_tick = start_tick;
+    loop {
+        let cmd_header = match demo_file.read_cmd_header() {
+            Ok(cmd_header) => cmd_header,
+            // NOTE: replay may be truncated
+            Err(_) if demo_file.is_at_eof().unwrap_or_default() => break,
+            Err(err) => return Err(err.into()),
+        };
+        if cmd_header.tick > end_tick
+            || matches!(
+                cmd_header.cmd,
+                EDemoCommands::DemStop | EDemoCommands::DemFileInfo | EDemoCommands::DemSpawnGroups
+            )
+        {
+            break;
+        }
+        let cmd_body = demo_file.read_cmd(&cmd_header)?;
+        demo_writer.write_cmd_with_header(&cmd_header, cmd_body)?;
+        last_tick = cmd_header.tick;
+    }
+
+    demo_writer.write_cmd(EDemoCommands::DemStop, last_tick, &[], false)?;
+
+    if let Ok(Some(spawn_groups)) = demo_file.spawn_groups() {
+        let cmd = CDemoSpawnGroups {
+            msgs: spawn_groups.iter().map(Message::encode_to_vec).collect(),
+        };
+        demo_writer.write_cmd(
+            EDemoCommands::DemSpawnGroups,
+            last_tick,
+            &cmd.encode_to_vec(),
+            false,
+        )?;
+    }
+
+    let mut file_info = demo_file.file_info().cloned().unwrap_or_default();
+    // NOTE: ticks are not being shifted, thus playback ticks (which DemoFile uses as total ticks)
+    // must remain to be the last tick.
+    let seconds_per_tick = if file_info.playback_ticks() > 0 {
+        file_info.playback_time() / file_info.playback_ticks() as f32
+    } else {
+        0.0
+    };
+    let frames_per_tick = if file_info.playback_ticks() > 0 {
+        file_info.playback_frames() as f32 / file_info.playback_ticks() as f32
+    } else {
+        0.0
+    };
+    file_info.playback_ticks = Some(last_tick);
+    file_info.playback_time = Some(last_tick as f32 * seconds_per_tick);
+    file_info.playback_frames = Some((last_tick as f32 * frames_per_tick) as i32);
+    demo_writer.write_file_info(last_tick, &file_info)?;
+
+    Ok(demo_writer.finish()?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::fieldvalue::FieldValue;
+    use crate::parser::{Context, Parser};
+    use crate::testdemo::{
+        ALIVE, BODY, BODY_CELL_X, HEALTH, ROUND, TEST_ENTITY_CLASS_ID, TEST_GAME_RULES_CLASS_ID,
+        TestDemoBuilder, VALUES, entities_state, string_tables_state,
+    };
+
+    // full packets are at ticks 10, 1810 and 3610. instancebaseline changes at ticks 10 and 1810;
+    // the latter change is only within the full packet's packet.
+    fn sample_replay() -> Result<Vec<u8>, anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(100))])
+            .create_entity(
+                1,
+                TEST_GAME_RULES_CLASS_ID,
+                1,
+                &[(ROUND, FieldValue::U64(1))],
+            )
+            .create_entity(
+                2,
+                TEST_ENTITY_CLASS_ID,
+                1,
+                &[
+                    (VALUES, FieldValue::U64(2)),
+                    (&[2, 0], FieldValue::U64(7)),
+                    (&[2, 1], FieldValue::U64(8)),
+                    (BODY, FieldValue::Bool(true)),
+                    (BODY_CELL_X, FieldValue::U64(10)),
+                ],
+            )
+            .write_packet(10)?
+            .write_full_packet(10)?;
+
+        for tick in (40..4000).step_by(30) {
+            builder.update_entity(2, &[(HEALTH, FieldValue::I64(tick as i64))]);
+            match tick {
+                1810 => {
+                    builder
+                        .update_entity(1, &[(ROUND, FieldValue::U64(2))])
+                        .write_packet(tick)?
+                        .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(50))])
+                        .write_full_packet(tick)?;
+                    continue;
+                }
+                2020 => {
+                    builder.create_entity(
+                        3,
+                        TEST_ENTITY_CLASS_ID,
+                        2,
+                        &[(ALIVE, FieldValue::Bool(true))],
+                    );
+                }
+                2500 => {
+                    builder
+                        .update_entity(1, &[(ROUND, FieldValue::U64(3))])
+                        .delete_entity(3);
+                }
+                3010 => {
+                    builder.create_entity(3, TEST_ENTITY_CLASS_ID, 3, &[]);
+                }
+                3610 => {
+                    builder.write_packet(tick)?.write_full_packet(tick)?;
+                    continue;
+                }
+                3700 => {
+                    builder.create_entity(
+                        4,
+                        TEST_ENTITY_CLASS_ID,
+                        1,
+                        &[(VALUES, FieldValue::U64(0))],
+                    );
+                }
+                _ => {}
+            }
+            builder.write_packet(tick)?;
+        }
+
+        builder.finish(4000)
+    }
+
+    fn assert_same_state(lhs: &Context, rhs: &Context) {
+        assert_eq!(entities_state(lhs), entities_state(rhs));
+        assert_eq!(string_tables_state(lhs), string_tables_state(rhs));
+    }
+
+    #[test]
+    fn test_clip_round_trip() -> Result<(), anyhow::Error> {
+        let data = sample_replay()?;
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(&data))?;
+        let clip_data = clip(&mut demo_file, Cursor::new(Vec::new()), 3650, 3900)?.into_inner();
+
+        let mut clip_file = DemoFile::start_reading(Cursor::new(&clip_data))?;
+        let full_packet = loop {
+            let cmd_header = clip_file.read_cmd_header()?;
+            if cmd_header.cmd == EDemoCommands::DemFullPacket {
+                break DemoFile::<Cursor<&Vec<u8>>>::decode_cmd_full_packet(
+                    clip_file.read_cmd(&cmd_header)?,
+                )?;
+            }
+            clip_file.skip_cmd(&cmd_header)?;
+        };
+        assert!(full_packet.string_table.is_some());
+
+        let mut parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&data))?)?;
+        let mut clip_parser =
+            Parser::from_stream(DemoFile::start_reading(Cursor::new(&clip_data))?)?;
+
+        for tick in [3650, 3800] {
+            parser.run_to_tick(tick)?;
+            clip_parser.run_to_tick(tick)?;
+            assert!(!entities_state(parser.context()).is_empty());
+            assert_same_state(parser.context(), clip_parser.context());
+        }
+
+        // NOTE: run_to_end ignores full packets; state comes from the synthesized packet.
+        parser.run_to_tick(3900)?;
+        let mut clip_parser =
+            Parser::from_stream(DemoFile::start_reading(Cursor::new(&clip_data))?)?;
+        clip_parser.run_to_end()?;
+        assert_same_state(parser.context(), clip_parser.context());
+
+        Ok(())
+    }
+}
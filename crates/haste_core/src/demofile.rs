@@ -3,11 +3,17 @@ use std::io::{self, Read, Seek, SeekFrom};
 use dungers::varint;
 use prost::Message;
 use valveprotos::common::{
-    CDemoClassInfo, CDemoFileInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables, EDemoCommands,
+    CDemoClassInfo, CDemoFileInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables,
+    CDemoSpawnGroups, EDemoCommands,
 };
 use valveprotos::prost;
 
-use crate::demostream::{CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError};
+use crate::demostream::{
+    scan_for_last_tick, CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError,
+    SeekableDemoStream,
+};
+use crate::matchsummary::MatchSummary;
+use crate::spawngroups::SpawnGroups;
 
 // #define DEMO_RECORD_BUFFER_SIZE 2*1024*1024
 //
@@ -20,7 +26,7 @@ pub const DEMO_RECORD_BUFFER_SIZE: usize = 2 * 1024 * 1024;
 //
 // NOTE: strings in c/cpp are null terminated.
 const DEMO_HEADER_ID_SIZE: usize = 8;
-const DEMO_HEADER_ID: [u8; DEMO_HEADER_ID_SIZE] = *b"PBDEMS2\0";
+pub(crate) const DEMO_HEADER_ID: [u8; DEMO_HEADER_ID_SIZE] = *b"PBDEMS2\0";
 
 // NOTE: naming is based on stuff from demofile.h of valve's demoinfo2 thing.
 #[derive(Debug, Clone)]
@@ -60,12 +66,23 @@ fn read_demo_header<R: Read>(mut rdr: R) -> Result<DemoHeader, DemoHeaderError>
     })
 }
 
+/// cmd that the demo header points at (file info, spawn groups) is not present in the replay; this
+/// is the case for replays that were cut short.
+#[derive(thiserror::Error, Debug)]
+#[error("replay does not contain {cmd:?} (offset {offset})")]
+pub struct MissingCmdError {
+    pub cmd: EDemoCommands,
+    pub offset: u64,
+}
+
 #[derive(Debug)]
 pub struct DemoFile<R: Read + Seek> {
     rdr: R,
     buf: Vec<u8>,
     demo_header: DemoHeader,
     file_info: Option<CDemoFileInfo>,
+    spawn_groups: Option<SpawnGroups>,
+    total_ticks: Option<i32>,
 }
 
 impl<R: Read + Seek> DemoFile<R> {
@@ -82,6 +99,8 @@ impl<R: Read + Seek> DemoFile<R> {
             buf: vec![0u8; DEMO_RECORD_BUFFER_SIZE],
             demo_header,
             file_info: None,
+            spawn_groups: None,
+            total_ticks: None,
         })
     }
 
@@ -90,18 +109,84 @@ impl<R: Read + Seek> DemoFile<R> {
         &self.demo_header
     }
 
+    /// reads [`CDemoFileInfo`] that is located at the end of the replay.
+    ///
+    /// replays that were cut short (crashed server, partial download) do not have it; in such
+    /// case [`MissingCmdError`] is returned.
     pub fn file_info(&mut self) -> Result<&CDemoFileInfo, anyhow::Error> {
         if self.file_info.is_none() {
-            let backup = self.stream_position()?;
+            let offset = self.demo_header.fileinfo_offset;
+            let cmd_body = self.read_cmd_at(offset, EDemoCommands::DemFileInfo)?;
+            self.file_info = Some(CDemoFileInfo::decode(cmd_body.as_slice())?);
+        }
+
+        Ok(self.file_info.as_ref().expect("file info have been read"))
+    }
 
-            self.seek(SeekFrom::Start(self.demo_header.fileinfo_offset as u64))?;
-            let cmd_header = self.read_cmd_header()?;
-            self.file_info = Some(CDemoFileInfo::decode(self.read_cmd(&cmd_header)?)?);
+    /// reads [`CDemoSpawnGroups`] that is located at the end of the replay (next to file info).
+    ///
+    /// returns `None` if the replay does not have spawn groups (spawngroups offset is 0).
+    pub fn spawn_groups(&mut self) -> Result<Option<&SpawnGroups>, anyhow::Error> {
+        if self.demo_header.spawngroups_offset == 0 {
+            return Ok(None);
+        }
 
-            self.seek(SeekFrom::Start(backup))?;
+        if self.spawn_groups.is_none() {
+            let offset = self.demo_header.spawngroups_offset;
+            let cmd_body = self.read_cmd_at(offset, EDemoCommands::DemSpawnGroups)?;
+            let cmd = CDemoSpawnGroups::decode(cmd_body.as_slice())?;
+            self.spawn_groups = Some(SpawnGroups::parse(cmd)?);
         }
 
-        Ok(self.file_info.as_ref().expect("file info have been read"))
+        Ok(self.spawn_groups.as_ref())
+    }
+
+    /// reads body of the cmd that is located at the given offset (as specified in the demo
+    /// header). stream position is restored afterwards.
+    fn read_cmd_at(&mut self, offset: i32, cmd: EDemoCommands) -> Result<Vec<u8>, anyhow::Error> {
+        let backup = self.stream_position()?;
+        let result = self.read_cmd_at_inner(offset, cmd);
+        self.seek(SeekFrom::Start(backup))?;
+        result
+    }
+
+    fn read_cmd_at_inner(
+        &mut self,
+        offset: i32,
+        cmd: EDemoCommands,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        // NOTE: offsets are being written into the header when recording is finished. they are 0
+        // in replays that were cut short, or they may point past the end of the stream if the
+        // replay got truncated after being finished (partial download).
+        let offset = offset as u64;
+        if offset < self.start_position() || offset >= self.stream_len()? {
+            return Err(MissingCmdError { cmd, offset }.into());
+        }
+
+        self.seek(SeekFrom::Start(offset))?;
+        let cmd_header = match self.read_cmd_header() {
+            Ok(cmd_header) => cmd_header,
+            Err(_) if self.is_at_or_past_eof()? => {
+                return Err(MissingCmdError { cmd, offset }.into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if cmd_header.cmd != cmd {
+            anyhow::bail!(
+                "unexpected cmd at offset {offset} (got {:?}; want {cmd:?})",
+                cmd_header.cmd,
+            );
+        }
+        if self.stream_position()? + cmd_header.body_size as u64 > self.stream_len()? {
+            return Err(MissingCmdError { cmd, offset }.into());
+        }
+        Ok(self.read_cmd(&cmd_header)?.to_vec())
+    }
+
+    /// builds [`MatchSummary`] out of [`CDemoFileInfo`] that is located at the end of the replay.
+    /// does not require parsing.
+    pub fn match_summary(&mut self) -> Result<MatchSummary, anyhow::Error> {
+        self.file_info().map(MatchSummary::from)
     }
 }
 
@@ -109,21 +194,14 @@ impl<R: Read + Seek> DemoStream for DemoFile<R> {
     // stream ops
     // ----
 
-    /// delegated from [`std::io::Seek`].
     #[inline]
-    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
-        self.rdr.seek(pos)
+    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
+        Ok(self.stream_position()? == self.stream_len()?)
     }
 
-    /// delegated from [`std::io::Seek`].
-    ///
-    /// # note
-    ///
-    /// be aware that this method can be quite expensive. it might be best to make sure not to call
-    /// it too frequently.
     #[inline]
-    fn stream_position(&mut self) -> Result<u64, io::Error> {
-        self.rdr.stream_position()
+    fn is_at_or_past_eof(&mut self) -> Result<bool, io::Error> {
+        Ok(self.stream_position()? >= self.stream_len()?)
     }
 
     // cmd header
@@ -213,14 +291,164 @@ impl<R: Read + Seek> DemoStream for DemoFile<R> {
         CDemoFullPacket::decode(data).map_err(DecodeCmdError::DecodeProtobufError)
     }
 
-    // other
-    // ----
+    #[inline]
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.seek(SeekFrom::Current(cmd_header.body_size as i64))
+            .map(|_| ())
+    }
+}
+
+impl<R: Read + Seek> SeekableDemoStream for DemoFile<R> {
+    /// delegated from [`std::io::Seek`].
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
+        self.rdr.seek(pos)
+    }
+
+    /// delegated from [`std::io::Seek`].
+    ///
+    /// # note
+    ///
+    /// be aware that this method can be quite expensive. it might be best to make sure not to call
+    /// it too frequently.
+    #[inline]
+    fn stream_position(&mut self) -> Result<u64, io::Error> {
+        self.rdr.stream_position()
+    }
 
     fn start_position(&self) -> u64 {
         size_of::<DemoHeader>() as u64
     }
 
+    /// returns playback ticks from [`CDemoFileInfo`]; if the replay does not have it (it was cut
+    /// short, see [`MissingCmdError`]) falls back to scanning the entire replay for the tick of the
+    /// last complete cmd. other errors are propagated.
     fn total_ticks(&mut self) -> Result<i32, anyhow::Error> {
-        self.file_info().map(|file_info| file_info.playback_ticks())
+        if let Some(total_ticks) = self.total_ticks {
+            return Ok(total_ticks);
+        }
+
+        let total_ticks = match self.file_info() {
+            Ok(file_info) => file_info.playback_ticks(),
+            Err(err) if !err.is::<MissingCmdError>() => return Err(err),
+            Err(_) => {
+                let backup = self.stream_position()?;
+                self.seek(SeekFrom::Start(self.start_position()))?;
+                let result = scan_for_last_tick(self);
+                self.seek(SeekFrom::Start(backup))?;
+                result?
+            }
+        };
+        self.total_ticks = Some(total_ticks);
+        Ok(total_ticks)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use valveprotos::common::{CDemoPacket, CnetMsgSpawnGroupLoad};
+
+    use super::*;
+    use crate::demowriter::DemoWriter;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_truncated() -> Result<(), anyhow::Error> {
+        let packet = CDemoPacket {
+            data: Some(vec![0; 64]),
+        }
+        .encode_to_vec();
+
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        demo_writer.write_cmd(EDemoCommands::DemSyncTick, -1, &[], false)?;
+        demo_writer.write_cmd(EDemoCommands::DemPacket, 1, &packet, false)?;
+        demo_writer.write_cmd(EDemoCommands::DemPacket, 2, &packet, false)?;
+        demo_writer.write_cmd(EDemoCommands::DemPacket, 3, &packet, false)?;
+        let mut data = demo_writer.finish()?.into_inner();
+        // NOTE: chop the replay in the middle of the last packet's body.
+        data.truncate(data.len() - 16);
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(&data))?;
+        assert!(
+            demo_file
+                .file_info()
+                .is_err_and(|err| err.is::<MissingCmdError>())
+        );
+        assert_eq!(demo_file.total_ticks()?, 2);
+
+        let mut parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&data))?)?;
+        assert!(parser.run_to_end().is_err());
+
+        let mut parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&data))?)?;
+        parser.set_allow_truncated(true);
+        parser.run_to_end()?;
+        assert_eq!(parser.context().tick(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_total_ticks_propagates_errors() -> Result<(), anyhow::Error> {
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        demo_writer.write_cmd(EDemoCommands::DemSyncTick, -1, &[], false)?;
+        // NOTE: file info is present, but it is garbage; this must not fall back to scanning.
+        demo_writer.write_cmd(EDemoCommands::DemFileInfo, 1, &[0xff], false)?;
+        let data = demo_writer.finish()?.into_inner();
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        assert!(
+            demo_file
+                .total_ticks()
+                .is_err_and(|err| !err.is::<MissingCmdError>())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spawn_groups() -> Result<(), anyhow::Error> {
+        let spawn_group = CnetMsgSpawnGroupLoad {
+            worldname: Some("maps/dota.vpk".to_string()),
+            ..Default::default()
+        };
+        let cmd = CDemoSpawnGroups {
+            msgs: vec![spawn_group.encode_to_vec()],
+        };
+
+        // spawngroups offset is 0
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        demo_writer.write_cmd(EDemoCommands::DemStop, 1, &[], false)?;
+        let data = demo_writer.finish()?.into_inner();
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        assert!(demo_file.spawn_groups()?.is_none());
+
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        demo_writer.write_cmd(EDemoCommands::DemStop, 1, &[], false)?;
+        demo_writer.write_cmd(
+            EDemoCommands::DemSpawnGroups,
+            1,
+            &cmd.encode_to_vec(),
+            false,
+        )?;
+        let mut data = demo_writer.finish()?.into_inner();
+        let mut demo_file = DemoFile::start_reading(Cursor::new(&data))?;
+        let world_name = demo_file
+            .spawn_groups()?
+            .and_then(|spawn_groups| spawn_groups.world_name().map(ToString::to_string));
+        assert_eq!(world_name.as_deref(), Some("maps/dota.vpk"));
+
+        // spawngroups offset points past the end of the stream (partial download)
+        let spawngroups_offset = demo_file.demo_header.spawngroups_offset as usize;
+        data.truncate(spawngroups_offset);
+        let mut demo_file = DemoFile::start_reading(Cursor::new(&data))?;
+        assert!(
+            demo_file
+                .spawn_groups()
+                .is_err_and(|err| err.is::<MissingCmdError>())
+        );
+
+        Ok(())
     }
 }
@@ -0,0 +1,228 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use bzip2::read::MultiBzDecoder;
+
+use crate::demofile::{DemoFile, DemoHeaderError};
+
+// NOTE: bzip2 streams are not seekable. blocks do exist within the stream, but they are bit
+// aligned (not byte aligned) and carry no index; finding them requires scanning the entire
+// stream. instead everything that was decompressed is retained in memory, which makes seeking
+// backwards free and seeking forwards as expensive as decompressing the skipped bytes.
+//
+// decompressed dota 2 replays are usually ~100 mb; this is the price for being able to seek.
+
+/// size of chunks that are being decompressed at once.
+const DECOMPRESS_CHUNK_SIZE: usize = 64 * 1024;
+
+/// [`Read`] + [`Seek`] adapter for bzip2-compressed streams.
+///
+/// decompression happens lazily, as data is being read (or seeked to). decompressed data is
+/// retained in memory which enables seeking.
+pub struct Bz2Reader<R: Read> {
+    decoder: MultiBzDecoder<R>,
+    buf: Vec<u8>,
+    pos: u64,
+    is_decoder_exhausted: bool,
+}
+
+impl<R: Read> Bz2Reader<R> {
+    /// creates a new [`Bz2Reader`] instance from the given (compressed) reader.
+    ///
+    /// # performance note
+    ///
+    /// for optimal performance make sure to provide a reader that implements buffering (for
+    /// example [`std::io::BufReader`]).
+    pub fn new(rdr: R) -> Self {
+        Self {
+            decoder: MultiBzDecoder::new(rdr),
+            buf: Vec::new(),
+            pos: 0,
+            is_decoder_exhausted: false,
+        }
+    }
+
+    /// decompresses until at least `len` bytes are available or the underlying stream is
+    /// exhausted.
+    fn fill_to(&mut self, len: u64) -> Result<(), io::Error> {
+        while !self.is_decoder_exhausted && (self.buf.len() as u64) < len {
+            let filled = self.buf.len();
+            self.buf.resize(filled + DECOMPRESS_CHUNK_SIZE, 0);
+            match self.decoder.read(&mut self.buf[filled..]) {
+                Ok(n) => {
+                    self.buf.truncate(filled + n);
+                    self.is_decoder_exhausted = n == 0;
+                }
+                Err(err) => {
+                    self.buf.truncate(filled);
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn fill_to_end(&mut self) -> Result<(), io::Error> {
+        self.fill_to(u64::MAX)
+    }
+}
+
+impl<R: Read> Read for Bz2Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill_to(self.pos + buf.len() as u64)?;
+
+        let start = (self.pos as usize).min(self.buf.len());
+        let n = (self.buf.len() - start).min(buf.len());
+        buf[..n].copy_from_slice(&self.buf[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Seek for Bz2Reader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.pos = n;
+                return Ok(n);
+            }
+            SeekFrom::End(n) => {
+                self.fill_to_end()?;
+                (self.buf.len() as u64, n)
+            }
+            SeekFrom::Current(n) => (self.pos, n),
+        };
+        match base.checked_add_signed(offset) {
+            Some(n) => {
+                self.pos = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    #[inline]
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+/// [`DemoFile`] that reads bzip2-compressed replays (`.dem.bz2`).
+pub type DemoFileBz2<R> = DemoFile<Bz2Reader<R>>;
+
+impl<R: Read> DemoFile<Bz2Reader<R>> {
+    /// creates a new [`DemoFileBz2`] instance from the given (compressed) reader.
+    ///
+    /// # performance note
+    ///
+    /// for optimal performance make sure to provide a reader that implements buffering (for
+    /// example [`std::io::BufReader`]).
+    pub fn start_reading_bz2(rdr: R) -> Result<Self, DemoHeaderError> {
+        Self::start_reading(Bz2Reader::new(rdr))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use bzip2::write::BzEncoder;
+    use bzip2::Compression;
+
+    use super::*;
+    use crate::fieldvalue::FieldValue;
+    use crate::parser::Parser;
+    use crate::testdemo::{
+        HEALTH, ROUND, TEST_ENTITY_CLASS_ID, TEST_GAME_RULES_CLASS_ID, TestDemoBuilder,
+        entities_state, string_tables_state,
+    };
+
+    #[test]
+    fn test_read_and_seek() -> Result<(), io::Error> {
+        let data: Vec<u8> = (0..DECOMPRESS_CHUNK_SIZE * 3)
+            .map(|i| (i % 251) as u8)
+            .collect();
+
+        let mut encoder = BzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&data)?;
+        let compressed = encoder.finish()?;
+
+        let mut rdr = Bz2Reader::new(compressed.as_slice());
+
+        let mut buf = [0u8; 16];
+        rdr.read_exact(&mut buf)?;
+        assert_eq!(&buf, &data[..16]);
+
+        // forward seek past decompressed data
+        let pos = rdr.seek(SeekFrom::Start(DECOMPRESS_CHUNK_SIZE as u64 * 2 + 7))?;
+        rdr.read_exact(&mut buf)?;
+        assert_eq!(&buf, &data[pos as usize..pos as usize + 16]);
+
+        // backward seek
+        rdr.seek(SeekFrom::Current(-32))?;
+        rdr.read_exact(&mut buf)?;
+        assert_eq!(&buf, &data[pos as usize - 16..pos as usize]);
+
+        assert_eq!(rdr.seek(SeekFrom::End(0))?, data.len() as u64);
+        assert_eq!(rdr.read(&mut buf)?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_to_tick() -> Result<(), anyhow::Error> {
+        // full packets are at ticks 10 and 1810.
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(100))])
+            .create_entity(
+                1,
+                TEST_GAME_RULES_CLASS_ID,
+                1,
+                &[(ROUND, FieldValue::U64(1))],
+            )
+            .create_entity(2, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(10)?
+            .write_full_packet(10)?;
+        for tick in (40..2400).step_by(30) {
+            builder.update_entity(2, &[(HEALTH, FieldValue::I64(tick as i64))]);
+            if tick == 2110 {
+                builder.create_entity(3, TEST_ENTITY_CLASS_ID, 1, &[]);
+            }
+            builder.write_packet(tick)?;
+            if tick == 1810 {
+                builder.write_full_packet(tick)?;
+            }
+        }
+        let data = builder.finish(2400)?;
+
+        let mut encoder = BzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&data)?;
+        let compressed = encoder.finish()?;
+
+        let mut parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&data))?)?;
+        let mut bz2_parser =
+            Parser::from_stream(DemoFile::start_reading_bz2(compressed.as_slice())?)?;
+        // NOTE: forwards past the full packet, then backwards.
+        for tick in [2200, 400, 2380] {
+            parser.run_to_tick(tick)?;
+            bz2_parser.run_to_tick(tick)?;
+            assert_eq!(bz2_parser.context().tick(), tick);
+            assert_eq!(
+                entities_state(bz2_parser.context()),
+                entities_state(parser.context())
+            );
+            assert_eq!(
+                string_tables_state(bz2_parser.context()),
+                string_tables_state(parser.context())
+            );
+        }
+
+        Ok(())
+    }
+}
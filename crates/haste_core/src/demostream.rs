@@ -42,35 +42,22 @@ pub enum DecodeCmdError {
     DecodeProtobufError(#[from] prost::DecodeError),
 }
 
-// TODO: is there a way to restrict (idk if this is a correct word) DemoStream trait so that it'll
-// require seek ops to be implemented only if the underlying thing binds to io::Seek trait? thus
-// making so that Parser will not provide methods (such as run_to_tick) that need seeking
-// capabilities?
+// NOTE: DemoStream covers what's needed to read cmds sequentially (this is all that's possible
+// with streams that are being received over the network); seeking capabilities are provided by
+// SeekableDemoStream. Parser exposes methods that need seeking (such as run_to_tick) only for
+// seekable streams.
 
 pub trait DemoStream {
     // stream ops
     // ----
 
-    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error>;
-
-    fn stream_position(&mut self) -> Result<u64, io::Error>;
-
-    /// reimplementation of nightly [`std::io::Seek::stream_len`].
-    fn stream_len(&mut self) -> Result<u64, io::Error> {
-        let old_pos = self.stream_position()?;
-        let len = self.seek(SeekFrom::End(0))?;
-
-        // avoid seeking a third time when we were already at the end of the
-        // stream. the branch is usually way cheaper than a seek operation.
-        if old_pos != len {
-            self.seek(SeekFrom::Start(old_pos))?;
-        }
+    fn is_at_eof(&mut self) -> Result<bool, io::Error>;
 
-        Ok(len)
-    }
-
-    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
-        Ok(self.stream_position()? == self.stream_len()?)
+    /// same as [`DemoStream::is_at_eof`], but also true if the stream was advanced past the end
+    /// (seeking past the end is not an error; see [`DemoStream::skip_cmd`]).
+    #[inline]
+    fn is_at_or_past_eof(&mut self) -> Result<bool, io::Error> {
+        self.is_at_eof()
     }
 
     // cmd header
@@ -78,12 +65,6 @@ pub trait DemoStream {
 
     fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError>;
 
-    #[inline]
-    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
-        self.seek(SeekFrom::Current(-(cmd_header.size as i64)))
-            .map(|_| ())
-    }
-
     // cmd
     // ----
 
@@ -113,17 +94,72 @@ pub trait DemoStream {
     // Max
     // IsCompressed (flag)
 
+    /// advances the stream past cmd's body. seekable streams seek, others have to read.
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error>;
+}
+
+pub trait SeekableDemoStream: DemoStream {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error>;
+
+    fn stream_position(&mut self) -> Result<u64, io::Error>;
+
+    /// reimplementation of nightly [`std::io::Seek::stream_len`].
+    fn stream_len(&mut self) -> Result<u64, io::Error> {
+        let old_pos = self.stream_position()?;
+        let len = self.seek(SeekFrom::End(0))?;
+
+        // avoid seeking a third time when we were already at the end of the
+        // stream. the branch is usually way cheaper than a seek operation.
+        if old_pos != len {
+            self.seek(SeekFrom::Start(old_pos))?;
+        }
+
+        Ok(len)
+    }
+
     #[inline]
-    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
-        self.seek(SeekFrom::Current(cmd_header.body_size as i64))
+    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.seek(SeekFrom::Current(-(cmd_header.size as i64)))
             .map(|_| ())
     }
 
-    // other
-    // ----
-
     fn start_position(&self) -> u64;
 
     // TODO: how not cool is it to rely on anyhow here?
     fn total_ticks(&mut self) -> Result<i32, anyhow::Error>;
 }
+
+/// scans the stream for the tick of the last complete cmd; this is useful for streams that do not
+/// carry total tick count (broadcasts; truncated replays that lack file info trailer).
+///
+/// scanning starts from the current stream position. stream position gets restored when done.
+///
+/// # note
+///
+/// cmd that is cut short (its body does not fit into the stream) is not considered.
+pub fn scan_for_last_tick(demo_stream: &mut impl SeekableDemoStream) -> Result<i32, anyhow::Error> {
+    let backup = demo_stream.stream_position()?;
+    let stream_len = demo_stream.stream_len()?;
+
+    let mut last_tick: i32 = -1;
+    loop {
+        match demo_stream.read_cmd_header() {
+            Ok(cmd_header) => {
+                demo_stream.skip_cmd(&cmd_header)?;
+                // NOTE: seeking past the end of the stream is not an error.
+                if demo_stream.stream_position()? > stream_len {
+                    break;
+                }
+                last_tick = cmd_header.tick;
+            }
+            Err(_) if demo_stream.stream_position()? >= stream_len => break,
+            Err(err) => {
+                demo_stream.seek(SeekFrom::Start(backup))?;
+                return Err(err.into());
+            }
+        }
+    }
+
+    demo_stream.seek(SeekFrom::Start(backup))?;
+    Ok(last_tick)
+}
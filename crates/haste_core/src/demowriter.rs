@@ -0,0 +1,300 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+use valveprotos::common::{CDemoFileInfo, EDemoCommands};
+use valveprotos::prost::Message;
+
+use crate::demofile::{DemoHeader, DEMO_HEADER_ID};
+use crate::demostream::CmdHeader;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WriteCmdError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    CompressError(#[from] snap::Error),
+    #[error("offset {0} does not fit into demo header")]
+    OffsetOverflow(u64),
+}
+
+// NOTE: this is an inverse of dungers::varint::read_uvarint32.
+fn write_uvarint32<W: Write>(mut wtr: W, mut value: u32) -> Result<usize, io::Error> {
+    let mut buf = [0u8; 5];
+    let mut n = 0;
+    while value >= 0x80 {
+        buf[n] = (value as u8) | 0x80;
+        value >>= 7;
+        n += 1;
+    }
+    buf[n] = value as u8;
+    n += 1;
+    wtr.write_all(&buf[..n])?;
+    Ok(n)
+}
+
+/// writes commands into a valid demo file (the inverse of [`crate::demofile::DemoFile`]).
+///
+/// header gets written as soon as writing starts, but offsets in it (fileinfo and spawngroups) are
+/// not known until [`DemoWriter::finish`] is called; they are being recorded as
+/// [`EDemoCommands::DemFileInfo`] and [`EDemoCommands::DemSpawnGroups`] cmds are written.
+pub struct DemoWriter<W: Write + Seek> {
+    wtr: W,
+    buf: Vec<u8>,
+    /// stream position at which the demo header starts. offsets in the header are relative to
+    /// it.
+    start_position: u64,
+    /// number of bytes written since `start_position`.
+    ///
+    /// NOTE: it's tracked manually because `stream_position` of [`std::io::BufWriter`] flushes.
+    num_bytes_written: u64,
+    demo_header: DemoHeader,
+}
+
+impl<W: Write + Seek> DemoWriter<W> {
+    /// creates a new [`DemoWriter`] instance that writes into the given writer.
+    ///
+    /// # performance note
+    ///
+    /// for optimal performance make sure to provide a writer that implements buffering (for
+    /// example [`std::io::BufWriter`]).
+    pub fn start_writing(mut wtr: W) -> Result<Self, io::Error> {
+        let start_position = wtr.stream_position()?;
+        let demo_header = DemoHeader {
+            demofilestamp: DEMO_HEADER_ID,
+            fileinfo_offset: 0,
+            spawngroups_offset: 0,
+        };
+        let num_bytes_written = write_demo_header(&mut wtr, &demo_header)? as u64;
+        Ok(Self {
+            wtr,
+            buf: Vec::new(),
+            start_position,
+            num_bytes_written,
+            demo_header,
+        })
+    }
+
+    /// writes cmd header followed by body. if `compress` is true the body will be compressed
+    /// (with snappy).
+    pub fn write_cmd(
+        &mut self,
+        cmd: EDemoCommands,
+        tick: i32,
+        body: &[u8],
+        compress: bool,
+    ) -> Result<(), WriteCmdError> {
+        if matches!(
+            cmd,
+            EDemoCommands::DemFileInfo | EDemoCommands::DemSpawnGroups
+        ) {
+            let offset = i32::try_from(self.num_bytes_written)
+                .map_err(|_| WriteCmdError::OffsetOverflow(self.num_bytes_written))?;
+            if cmd == EDemoCommands::DemFileInfo {
+                self.demo_header.fileinfo_offset = offset;
+            } else {
+                self.demo_header.spawngroups_offset = offset;
+            }
+        }
+
+        let body = if compress {
+            self.buf.resize(snap::raw::max_compress_len(body.len()), 0);
+            let n = snap::raw::Encoder::new().compress(body, &mut self.buf)?;
+            &self.buf[..n]
+        } else {
+            body
+        };
+
+        let mut cmd_raw = cmd as u32;
+        if compress {
+            cmd_raw |= EDemoCommands::DemIsCompressed as u32;
+        }
+        let mut n = write_uvarint32(&mut self.wtr, cmd_raw)?;
+        // NOTE: ticks of initialization cmds are -1, which is u32::MAX on the wire; see
+        // DemoFile's read_cmd_header.
+        n += write_uvarint32(&mut self.wtr, tick as u32)?;
+        n += write_uvarint32(&mut self.wtr, body.len() as u32)?;
+        self.wtr.write_all(body)?;
+        n += body.len();
+        self.num_bytes_written += n as u64;
+
+        Ok(())
+    }
+
+    /// writes cmd that was read from a [`crate::demostream::DemoStream`]. `body` is expected to be
+    /// uncompressed (as returned by `read_cmd`); it is being re-compressed if the original cmd was
+    /// compressed.
+    #[inline]
+    pub fn write_cmd_with_header(
+        &mut self,
+        cmd_header: &CmdHeader,
+        body: &[u8],
+    ) -> Result<(), WriteCmdError> {
+        self.write_cmd(
+            cmd_header.cmd,
+            cmd_header.tick,
+            body,
+            cmd_header.body_compressed,
+        )
+    }
+
+    pub fn write_file_info(
+        &mut self,
+        tick: i32,
+        file_info: &CDemoFileInfo,
+    ) -> Result<(), WriteCmdError> {
+        let body = file_info.encode_to_vec();
+        self.write_cmd(EDemoCommands::DemFileInfo, tick, &body, false)
+    }
+
+    #[inline]
+    pub fn demo_header(&self) -> &DemoHeader {
+        &self.demo_header
+    }
+
+    /// patches offsets in the demo header and flushes the writer. returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        let end_position = self.start_position + self.num_bytes_written;
+        self.wtr.seek(SeekFrom::Start(self.start_position))?;
+        write_demo_header(&mut self.wtr, &self.demo_header)?;
+        self.wtr.seek(SeekFrom::Start(end_position))?;
+        self.wtr.flush()?;
+        Ok(self.wtr)
+    }
+}
+
+/// returns the number of bytes written.
+fn write_demo_header<W: Write>(mut wtr: W, demo_header: &DemoHeader) -> Result<usize, io::Error> {
+    let fileinfo_offset = demo_header.fileinfo_offset.to_le_bytes();
+    let spawngroups_offset = demo_header.spawngroups_offset.to_le_bytes();
+    wtr.write_all(&demo_header.demofilestamp)?;
+    wtr.write_all(&fileinfo_offset)?;
+    wtr.write_all(&spawngroups_offset)?;
+    Ok(demo_header.demofilestamp.len() + fileinfo_offset.len() + spawngroups_offset.len())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufWriter, Cursor};
+
+    use valveprotos::common::{CDemoFileHeader, CDemoPacket};
+
+    use super::*;
+    use crate::demofile::DemoFile;
+    use crate::demostream::{DemoStream, SeekableDemoStream};
+    use crate::fieldvalue::FieldValue;
+    use crate::parser::Parser;
+    use crate::testdemo::{
+        HEALTH, ROUND, TEST_ENTITY_CLASS_ID, TEST_GAME_RULES_CLASS_ID, TestDemoBuilder,
+        entities_state, string_tables_state,
+    };
+
+    #[test]
+    fn test_write_and_read() -> Result<(), anyhow::Error> {
+        let file_header = CDemoFileHeader {
+            demo_file_stamp: "PBDEMS2\0".to_string(),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let packet = CDemoPacket {
+            data: Some((0..1024).map(|i| (i % 7) as u8).collect()),
+        }
+        .encode_to_vec();
+        let file_info = CDemoFileInfo {
+            playback_ticks: Some(42),
+            ..Default::default()
+        };
+
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        demo_writer.write_cmd(EDemoCommands::DemFileHeader, -1, &file_header, false)?;
+        demo_writer.write_cmd(EDemoCommands::DemSyncTick, -1, &[], false)?;
+        demo_writer.write_cmd(EDemoCommands::DemPacket, 300, &packet, true)?;
+        demo_writer.write_cmd(EDemoCommands::DemStop, 42, &[], false)?;
+        demo_writer.write_file_info(42, &file_info)?;
+        let data = demo_writer.finish()?.into_inner();
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut expect = |cmd: EDemoCommands, tick: i32, body: &[u8], compressed: bool| {
+            let cmd_header = demo_file.read_cmd_header()?;
+            assert_eq!(cmd_header.cmd, cmd);
+            assert_eq!(cmd_header.tick, tick);
+            assert_eq!(cmd_header.body_compressed, compressed);
+            assert_eq!(demo_file.read_cmd(&cmd_header)?, body);
+            Ok::<_, anyhow::Error>(())
+        };
+        expect(EDemoCommands::DemFileHeader, -1, &file_header, false)?;
+        expect(EDemoCommands::DemSyncTick, -1, &[], false)?;
+        expect(EDemoCommands::DemPacket, 300, &packet, true)?;
+        expect(EDemoCommands::DemStop, 42, &[], false)?;
+
+        assert_eq!(demo_file.file_info()?.playback_ticks(), 42);
+        assert_eq!(demo_file.total_ticks()?, 42);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(100))])
+            .create_entity(
+                1,
+                TEST_GAME_RULES_CLASS_ID,
+                1,
+                &[(ROUND, FieldValue::U64(1))],
+            )
+            .create_entity(2, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(10)?
+            .write_full_packet(10)?;
+        for tick in (40..400).step_by(30) {
+            builder.update_entity(2, &[(HEALTH, FieldValue::I64(tick as i64))]);
+            if tick == 250 {
+                builder.create_entity(3, TEST_ENTITY_CLASS_ID, 1, &[]);
+            }
+            builder.write_packet(tick)?;
+        }
+        let data = builder.finish(400)?;
+
+        // NOTE: buffered writer is what DemoWriter is supposed to be used with.
+        let mut demo_file = DemoFile::start_reading(Cursor::new(&data))?;
+        let mut demo_writer = DemoWriter::start_writing(BufWriter::new(Cursor::new(Vec::new())))?;
+        loop {
+            let cmd_header = match demo_file.read_cmd_header() {
+                Ok(cmd_header) => cmd_header,
+                Err(_) if demo_file.is_at_eof()? => break,
+                Err(err) => return Err(err.into()),
+            };
+            demo_writer.write_cmd_with_header(&cmd_header, demo_file.read_cmd(&cmd_header)?)?;
+        }
+        let written = demo_writer.finish()?.into_inner()?.into_inner();
+
+        let mut parser = Parser::from_stream(DemoFile::start_reading(Cursor::new(&data))?)?;
+        parser.run_to_end()?;
+        let mut written_demo_file = DemoFile::start_reading(Cursor::new(&written))?;
+        assert_eq!(written_demo_file.total_ticks()?, 400);
+        let mut written_parser = Parser::from_stream(written_demo_file)?;
+        written_parser.run_to_end()?;
+
+        assert_eq!(entities_state(written_parser.context()).len(), 3);
+        assert_eq!(
+            entities_state(written_parser.context()),
+            entities_state(parser.context())
+        );
+        assert_eq!(
+            string_tables_state(written_parser.context()),
+            string_tables_state(parser.context())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_offset_overflow() -> Result<(), anyhow::Error> {
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        // NOTE: regular cmds do not care about offsets, but header can't point past i32::MAX.
+        demo_writer.num_bytes_written = i32::MAX as u64 + 1;
+        demo_writer.write_cmd(EDemoCommands::DemStop, 42, &[], false)?;
+        let result = demo_writer.write_file_info(42, &CDemoFileInfo::default());
+        assert!(matches!(result, Err(WriteCmdError::OffsetOverflow(_))));
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::{self, Binary};
+use std::fmt::{self, Binary, Write as _};
 use std::hash::BuildHasherDefault;
 use std::rc::Rc;
 
@@ -7,8 +7,11 @@ use hashbrown::HashMap;
 use nohash::NoHashHasher;
 
 use crate::bitreader::{BitReader, BitReaderOverflowError};
+#[cfg(any(test, feature = "test-util"))]
+use crate::bitwriter::BitWriter;
 use crate::entityclasses::EntityClasses;
 use crate::fielddecoder::FieldDecodeContext;
+use crate::fieldkey;
 use crate::fieldpath::{self, FieldPath};
 use crate::fieldvalue::{FieldValue, FieldValueConversionError};
 use crate::flattenedserializers::{
@@ -16,6 +19,7 @@ use crate::flattenedserializers::{
 };
 use crate::fxhash;
 use crate::instancebaseline::InstanceBaseline;
+use crate::symboltable::{Symbol, SymbolTable};
 
 #[derive(thiserror::Error, Debug)]
 pub enum GetValueError {
@@ -50,14 +54,30 @@ pub fn ehandle_to_index(handle: u32) -> i32 {
     (handle & ((1 << MAX_EDICT_BITS) - 1)) as i32
 }
 
+const NETWORKED_EHANDLE_SERIAL_NUMBER_MASK: u32 =
+    (1 << NUM_NETWORKED_EHANDLE_SERIAL_NUMBER_BITS) - 1;
+
+/// returns serial number bits of the networked handle. note that networked handles carry only
+/// lower [`NUM_NETWORKED_EHANDLE_SERIAL_NUMBER_BITS`] bits of entity's serial number.
+pub fn ehandle_to_serial(handle: u32) -> u32 {
+    (handle >> MAX_EDICT_BITS) & NETWORKED_EHANDLE_SERIAL_NUMBER_MASK
+}
+
+// public/basehandle.h (adjusted to networked handles)
+// CBaseHandle::Init
+// m_Index = iEntry | (iSerialNumber << NUM_SERIAL_NUM_SHIFT_BITS);
+
+/// builds networked handle out of entity's index and serial number (serial number is truncated
+/// to fit). this is the inverse of [`ehandle_to_index`] and [`ehandle_to_serial`].
+pub fn ehandle_from_parts(index: i32, serial: u32) -> u32 {
+    (index as u32 & ((1 << MAX_EDICT_BITS) - 1))
+        | ((serial & NETWORKED_EHANDLE_SERIAL_NUMBER_MASK) << MAX_EDICT_BITS)
+}
+
 // TODO(blukai): investigate this (from public/basehandle.h):
 // > The low NUM_SERIAL_BITS hold the index. If this value is less than MAX_EDICTS, then the entity is networkable.
 // > The high NUM_SERIAL_NUM_BITS bits are the serial number.
 
-// NOTE(blukai): can converting index and serial to handle (what CBaseHandle::Init (in
-// public/basehandle.h) does) be somehow somewhere useful?:
-// m_Index = iEntry | (iSerialNumber << NUM_SERIAL_NUM_SHIFT_BITS);
-
 // NOTE: rust want that coord_from_cell is never used, but that is because there are no default
 // features that indicate otherwise (both deadlock and dota2 features are not active by default).
 #[allow(dead_code)]
@@ -134,24 +154,8 @@ mod dota2 {
 #[cfg(feature = "dota2")]
 pub use dota2::coord_from_cell as dota2_coord_from_cell;
 
-/// generates field key from given path. can and recommended to be called from a const context.
-/// when called from a const context, the function is interpreted by the compiler at compile time
-/// meaning that there's no const of generating key for given path at runtime.
-pub const fn fkey_from_path(path: &[&str]) -> u64 {
-    assert!(path.len() > 0, "invalid path");
-
-    let seed = fxhash::hash_bytes(path[0].as_bytes());
-    let mut hash = seed;
-
-    let mut i = 1;
-    while i < path.len() {
-        let part = fxhash::hash_bytes(path[i].as_bytes());
-        hash = fxhash::add_u64_to_hash(hash, part);
-        i += 1;
-    }
-
-    hash
-}
+// NOTE: fkey_from_path is re-exported so that existing imports keep working; see fieldkey.rs.
+pub use crate::fieldkey::fkey_from_path;
 
 // csgo srcs:
 // - CL_ParseDeltaHeader in engine/client.cpp.
@@ -197,6 +201,24 @@ impl DeltaHeader {
         br.read_bits(&mut buf, 2);
         Self(buf[0])
     }
+
+    /// inverse of [`Self::from_bit_reader`].
+    #[cfg(any(test, feature = "test-util"))]
+    #[inline]
+    pub(crate) fn write(self, bw: &mut BitWriter) {
+        bw.write_ubit64(self.0 as u64, 2);
+    }
+}
+
+/// what [`Entity`] was created from (see [`Entity::created_from`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityCreatedFrom {
+    /// entity was created by a delta packet (baseline + delta); this means that creation tick is
+    /// the actual tick at which the entity was created.
+    Delta,
+    /// entity was created while processing a full packet (a snapshot; for example after seeking).
+    /// it might have existed before its creation tick.
+    FullPacket,
 }
 
 #[derive(Debug, Clone)]
@@ -206,11 +228,153 @@ struct EntityField {
     value: FieldValue,
 }
 
+/// tree view of [`Entity`] (see [`Entity::tree`]); shape follows entity's serializer, nodes
+/// exist only for fields that have values.
+#[derive(Debug, Clone)]
+pub enum EntityNode<'a> {
+    /// serializer or a component (for example `CBodyComponent`); names are resolved with
+    /// [`crate::parser::Context::symbols`].
+    Struct(Vec<(Symbol, EntityNode<'a>)>),
+    /// fixed or dynamic array; elements are paired with their indices because not all of them
+    /// necessarily have values.
+    Array(Vec<(usize, EntityNode<'a>)>),
+    Value(&'a FieldValue),
+}
+
+impl<'a> EntityNode<'a> {
+    /// looks up direct child of a struct node by name hash.
+    pub fn get(&self, name_hash: u64) -> Option<&EntityNode<'a>> {
+        match self {
+            Self::Struct(fields) => fields
+                .iter()
+                .find(|(name, _)| name.hash == name_hash)
+                .map(|(_, node)| node),
+            _ => None,
+        }
+    }
+
+    /// looks up element of an array node by index.
+    pub fn index(&self, index: usize) -> Option<&EntityNode<'a>> {
+        match self {
+            Self::Array(elements) => elements
+                .iter()
+                .find(|(i, _)| *i == index)
+                .map(|(_, node)| node),
+            _ => None,
+        }
+    }
+
+    pub fn as_value(&self) -> Option<&'a FieldValue> {
+        match self {
+            Self::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+type EntityFieldMap = HashMap<u64, EntityField, BuildHasherDefault<NoHashHasher<u64>>>;
+
+/// value of a dynamic array field is its length.
+#[inline]
+fn array_len_from_value(value: &FieldValue) -> Option<usize> {
+    match value {
+        FieldValue::U64(len) => Some(*len as usize),
+        _ => None,
+    }
+}
+
+/// removes value of the field and values of everything beneath it.
+fn remove_field_subtree(fields: &mut EntityFieldMap, field: &FlattenedSerializerField, key: u64) {
+    let removed = fields.remove(&key);
+    remove_field_children(fields, field, key, removed.as_ref().map(|ef| &ef.value));
+}
+
+/// removes values of everything beneath the field. value of the field itself is needed to know
+/// length of dynamic arrays.
+fn remove_field_children(
+    fields: &mut EntityFieldMap,
+    field: &FlattenedSerializerField,
+    key: u64,
+    value: Option<&FieldValue>,
+) {
+    let Some(field_serializer) = field.field_serializer.as_ref() else {
+        return;
+    };
+
+    if field.is_dynamic_array() {
+        let len = value.and_then(array_len_from_value).unwrap_or_default();
+        remove_array_elements(fields, field, key, 0..len);
+    } else if field.is_fixed_array() {
+        for (i, element) in field_serializer.fields.iter().enumerate() {
+            let element_key = fxhash::add_u64_to_hash(key, fieldkey::index_hash(i as u64));
+            remove_field_subtree(fields, element, element_key);
+        }
+    } else {
+        for child in field_serializer.fields.iter() {
+            let child_key = fxhash::add_u64_to_hash(key, child.var_name.hash);
+            remove_field_subtree(fields, child, child_key);
+        }
+    }
+}
+
+fn remove_array_elements(
+    fields: &mut EntityFieldMap,
+    array_field: &FlattenedSerializerField,
+    array_key: u64,
+    indices: std::ops::Range<usize>,
+) {
+    let Some(element) = array_field.get_child(0) else {
+        return;
+    };
+    for i in indices {
+        let element_key = fxhash::add_u64_to_hash(array_key, fieldkey::index_hash(i as u64));
+        remove_field_subtree(fields, element, element_key);
+    }
+}
+
+/// drops values that the incoming value of the field makes stale. must be called before the
+/// incoming value is stored.
+#[inline]
+fn prune_stale_fields(
+    fields: &mut EntityFieldMap,
+    field: &FlattenedSerializerField,
+    key: u64,
+    value: &FieldValue,
+) {
+    // NOTE: when dynamic array shrinks elements that are beyond its new length are not being sent
+    // (they are garbage), thus they need to be dropped; see
+    // https://github.com/markus-wa/demoinfocs-golang/issues/450 for details.
+    if field.is_dynamic_array() {
+        let prev_len = fields
+            .get(&key)
+            .and_then(|ef| array_len_from_value(&ef.value));
+        if let (Some(prev_len), Some(len)) = (prev_len, array_len_from_value(value)) {
+            if len < prev_len {
+                remove_array_elements(fields, field, key, len..prev_len);
+            }
+        }
+    } else if field.is_pointer() && matches!(value, FieldValue::Bool(false)) {
+        // NOTE: pointer that became false means that the object it was pointing to is gone (for
+        // example a component), along with all of its fields. if it wasn't true there's nothing
+        // beneath it, no need to walk the subtree.
+        let prev_value = fields.get(&key).map(|ef| &ef.value);
+        if matches!(prev_value, Some(FieldValue::Bool(true))) {
+            remove_field_children(fields, field, key, None);
+        }
+    }
+}
+
 // TODO: do not publicly expose Entity's fields
 #[derive(Debug, Clone)]
 pub struct Entity {
     index: i32,
-    fields: HashMap<u64, EntityField, BuildHasherDefault<NoHashHasher<u64>>>,
+    serial: u32,
+    class_id: i32,
+    created_tick: i32,
+    updated_tick: i32,
+    update_count: u32,
+    created_from: EntityCreatedFrom,
+    fields: EntityFieldMap,
     serializer: Rc<FlattenedSerializer>,
 }
 
@@ -239,13 +403,18 @@ impl Entity {
                 for i in 1..=fp.last() {
                     if field.is_dynamic_array() {
                         field = field.get_child_unchecked(0);
-                        // NOTE: it's sort of weird to hash index, yup. but it simplifies things
-                        // when "user" builds a key that has numbers / it makes it so that there's
-                        // no need to check whether part of a key needs to be hashed or not - just
-                        // hash all parts.
                         field_key = fxhash::add_u64_to_hash(
                             field_key,
-                            fxhash::add_u64_to_hash(0, fp.get_unchecked(i) as u64),
+                            fieldkey::index_hash(fp.get_unchecked(i) as u64),
+                        );
+                    } else if field.is_fixed_array() {
+                        // NOTE: elements of fixed arrays share var name with the array, thus
+                        // index must be hashed (same as for dynamic arrays), otherwise all
+                        // elements would end up under the same key.
+                        field = field.get_child_unchecked(fp.get_unchecked(i));
+                        field_key = fxhash::add_u64_to_hash(
+                            field_key,
+                            fieldkey::index_hash(fp.get_unchecked(i) as u64),
                         );
                     } else {
                         field = field.get_child_unchecked(fp.get_unchecked(i));
@@ -259,6 +428,8 @@ impl Entity {
 
                 // eprintln!(" -> {:?}", &field_value);
 
+                prune_stale_fields(&mut self.fields, field, field_key, &field_value);
+
                 match self.fields.entry(field_key) {
                     Entry::Occupied(mut oe) => {
                         oe.get_mut().value = field_value;
@@ -321,11 +492,221 @@ impl Entity {
         )
     }
 
+    /// length of the dynamic array field with the provided key (for example
+    /// `m_vecPlayerData`). elements that are beyond the length are not being kept.
+    pub fn array_len(&self, key: &u64) -> Option<usize> {
+        self.fields
+            .get(key)
+            .and_then(|entity_field| array_len_from_value(&entity_field.value))
+    }
+
+    /// whether the object that pointer field with the provided key (for example
+    /// `CBodyComponent`) points to exists. fields of objects that do not exist are not being kept.
+    pub fn has_component(&self, key: &u64) -> bool {
+        self.get_value::<bool>(key).unwrap_or_default()
+    }
+
+    /// keys of elements of the dynamic array field with the provided key. elements of serializer
+    /// arrays have no values on their own, their keys are meant to be extended with names of
+    /// their fields (see [`crate::fieldkey::FieldKey`]).
+    pub fn array_element_keys(&self, key: &u64) -> impl Iterator<Item = u64> + '_ {
+        let key = *key;
+        (0..self.array_len(&key).unwrap_or_default())
+            .map(move |i| fxhash::add_u64_to_hash(key, fieldkey::index_hash(i as u64)))
+    }
+
+    /// iterates over values of elements of the dynamic array field with the provided key (for
+    /// example `m_vecDataTeam`) in order of their indices.
+    pub fn iter_array(&self, key: &u64) -> impl Iterator<Item = (usize, &FieldValue)> {
+        self.array_element_keys(key)
+            .enumerate()
+            .filter_map(|(i, element_key)| {
+                self.fields
+                    .get(&element_key)
+                    .map(|entity_field| (i, &entity_field.value))
+            })
+    }
+
     #[cfg(feature = "preserve-metadata")]
     pub fn get_path(&self, key: &u64) -> Option<&FieldPath> {
         self.fields.get(key).map(|ef| &ef.path)
     }
 
+    /// reverse lookup of field's dotted path (for example `m_vecPlayerData.3.m_iszPlayerName`)
+    /// by its key.
+    ///
+    /// this walks serializer's tree, it's not fast; meant for diagnostics and dumps. names are
+    /// resolved with [`crate::parser::Context::symbols`].
+    pub fn get_path_name(&self, key: &u64, symbols: &SymbolTable) -> Option<String> {
+        let mut path_name = None;
+        self.walk_path_names(symbols, &mut |field_key, path| {
+            if field_key == *key {
+                path_name = Some(path.to_string());
+                return true;
+            }
+            false
+        });
+        path_name
+    }
+
+    /// keys and dotted paths of all fields that have values, in serializer's order.
+    ///
+    /// this walks serializer's tree, it's not fast; meant for diagnostics and dumps.
+    pub fn path_names(&self, symbols: &SymbolTable) -> Vec<(u64, String)> {
+        let mut path_names = Vec::with_capacity(self.fields.len());
+        self.walk_path_names(symbols, &mut |field_key, path| {
+            if self.fields.contains_key(&field_key) {
+                path_names.push((field_key, path.to_string()));
+            }
+            false
+        });
+        path_names
+    }
+
+    // NOTE: keys are built exactly the same way as in [`Entity::parse`]. walking stops once
+    // visit returns true.
+    fn walk_path_names(
+        &self,
+        symbols: &SymbolTable,
+        visit: &mut impl FnMut(u64, &str) -> bool,
+    ) -> bool {
+        let mut path = String::new();
+        for field in self.serializer.fields.iter() {
+            path.clear();
+            path.push_str(symbols.resolve(field.var_name));
+            if self.walk_field_path_names(symbols, field, field.var_name.hash, &mut path, visit) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn walk_field_path_names(
+        &self,
+        symbols: &SymbolTable,
+        field: &FlattenedSerializerField,
+        field_key: u64,
+        path: &mut String,
+        visit: &mut impl FnMut(u64, &str) -> bool,
+    ) -> bool {
+        if visit(field_key, path) {
+            return true;
+        }
+
+        let Some(field_serializer) = field.field_serializer.as_ref() else {
+            return false;
+        };
+
+        let is_dynamic_array = field.is_dynamic_array();
+        let is_array = is_dynamic_array || field.is_fixed_array();
+        let len = if is_dynamic_array {
+            self.array_len(&field_key).unwrap_or_default()
+        } else {
+            field_serializer.fields.len()
+        };
+
+        let path_len = path.len();
+        for i in 0..len {
+            let child = if is_dynamic_array {
+                field_serializer.get_child(0)
+            } else {
+                field_serializer.get_child(i)
+            };
+            let Some(child) = child else {
+                break;
+            };
+
+            let child_key = if is_array {
+                let _ = write!(path, ".{i}");
+                fxhash::add_u64_to_hash(field_key, fieldkey::index_hash(i as u64))
+            } else {
+                path.push('.');
+                path.push_str(symbols.resolve(child.var_name));
+                fxhash::add_u64_to_hash(field_key, child.var_name.hash)
+            };
+
+            if self.walk_field_path_names(symbols, child, child_key, path, visit) {
+                return true;
+            }
+            path.truncate(path_len);
+        }
+
+        false
+    }
+
+    /// walks entity as a tree; fixed arrays, dynamic arrays and nested serializers (for example
+    /// `m_pGameRules`) become nodes of their own instead of being flattened into keys.
+    ///
+    /// this walks serializer's tree, it's not fast; meant for tools that need to render entities.
+    pub fn tree(&self) -> EntityNode<'_> {
+        EntityNode::Struct(
+            self.serializer
+                .fields
+                .iter()
+                .filter_map(|field| {
+                    self.field_node(field, field.var_name.hash)
+                        .map(|node| (field.var_name, node))
+                })
+                .collect(),
+        )
+    }
+
+    // NOTE: keys are built exactly the same way as in [`Entity::parse`].
+    fn field_node(
+        &self,
+        field: &FlattenedSerializerField,
+        field_key: u64,
+    ) -> Option<EntityNode<'_>> {
+        let Some(field_serializer) = field.field_serializer.as_ref() else {
+            return self
+                .fields
+                .get(&field_key)
+                .map(|ef| EntityNode::Value(&ef.value));
+        };
+
+        let element_key =
+            |i: usize| fxhash::add_u64_to_hash(field_key, fieldkey::index_hash(i as u64));
+
+        if field.is_dynamic_array() {
+            // NOTE: array that has no length has never been sent.
+            let len = self.array_len(&field_key)?;
+            let element = field_serializer.get_child(0)?;
+            Some(EntityNode::Array(
+                (0..len)
+                    .filter_map(|i| {
+                        self.field_node(element, element_key(i))
+                            .map(|node| (i, node))
+                    })
+                    .collect(),
+            ))
+        } else if field.is_fixed_array() {
+            let elements: Vec<_> = field_serializer
+                .fields
+                .iter()
+                .enumerate()
+                .filter_map(|(i, element)| {
+                    self.field_node(element, element_key(i))
+                        .map(|node| (i, node))
+                })
+                .collect();
+            (!elements.is_empty()).then_some(EntityNode::Array(elements))
+        } else {
+            if field.is_pointer() && !self.has_component(&field_key) {
+                return None;
+            }
+            let children: Vec<_> = field_serializer
+                .fields
+                .iter()
+                .filter_map(|child| {
+                    let child_key = fxhash::add_u64_to_hash(field_key, child.var_name.hash);
+                    self.field_node(child, child_key)
+                        .map(|node| (child.var_name, node))
+                })
+                .collect();
+            (!children.is_empty()).then_some(EntityNode::Struct(children))
+        }
+    }
+
     pub fn serializer(&self) -> &FlattenedSerializer {
         self.serializer.as_ref()
     }
@@ -344,6 +725,50 @@ impl Entity {
     pub fn index(&self) -> i32 {
         self.index
     }
+
+    /// see [`crate::entityclasses::EntityClasses::by_id`].
+    pub fn class_id(&self) -> i32 {
+        self.class_id
+    }
+
+    /// serial number distinguishes entities that occupied the same index at different times.
+    /// it is 0 for baseline entities.
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// networked handle of this entity (see [`ehandle_from_parts`]).
+    pub fn ehandle(&self) -> u32 {
+        ehandle_from_parts(self.index, self.serial)
+    }
+
+    /// tick at which the entity was created. see [`Entity::created_from`] for caveats.
+    pub fn created_tick(&self) -> i32 {
+        self.created_tick
+    }
+
+    /// tick of the last update; creation tick if the entity was never updated.
+    pub fn updated_tick(&self) -> i32 {
+        self.updated_tick
+    }
+
+    /// number of updates since creation (creation itself is not counted).
+    pub fn update_count(&self) -> u32 {
+        self.update_count
+    }
+
+    pub fn created_from(&self) -> EntityCreatedFrom {
+        self.created_from
+    }
+}
+
+/// things that [`EntityContainer::handle_create`] needs that do not change within a packet.
+pub(crate) struct EntityCreateContext<'a> {
+    pub(crate) tick: i32,
+    pub(crate) created_from: EntityCreatedFrom,
+    pub(crate) entity_classes: &'a EntityClasses,
+    pub(crate) instance_baseline: &'a InstanceBaseline,
+    pub(crate) serializers: &'a FlattenedSerializerContainer,
 }
 
 #[derive(Debug)]
@@ -359,6 +784,11 @@ pub struct EntityContainer {
     // FieldPathsReader there would be 2 levels of indirection (at least as i imagine it right
     // now).
     field_paths: Vec<FieldPath>,
+
+    // NOTE: indices of entities of each class are kept sorted.
+    by_class: HashMap<i32, Vec<i32>, BuildHasherDefault<NoHashHasher<i32>>>,
+    // NOTE: serializer name hash -> class id of classes that had at least one entity created.
+    class_ids: HashMap<u64, i32, BuildHasherDefault<NoHashHasher<u64>>>,
 }
 
 impl EntityContainer {
@@ -377,35 +807,54 @@ impl EntityContainer {
             // NOTE: 4096 is an arbitrary value that is large enough that that came out of printing
             // out count of fps collected per "run". (sort -nr can be handy)
             field_paths: vec![FieldPath::default(); 4096],
+
+            by_class: HashMap::default(),
+            class_ids: HashMap::default(),
         }
     }
 
     pub(crate) fn handle_create(
         &mut self,
         index: i32,
+        create_ctx: &EntityCreateContext,
         field_decode_ctx: &mut FieldDecodeContext,
         br: &mut BitReader,
-        entity_classes: &EntityClasses,
-        instance_baseline: &InstanceBaseline,
-        serializers: &FlattenedSerializerContainer,
     ) -> Result<&Entity, BitReaderOverflowError> {
+        let EntityCreateContext {
+            tick,
+            created_from,
+            entity_classes,
+            instance_baseline,
+            serializers,
+        } = *create_ctx;
+
         let class_id = br.read_ubit64(entity_classes.bits) as i32;
-        let _serial = br.read_ubit64(NUM_SERIAL_NUM_BITS as usize);
+        let serial = br.read_ubit64(NUM_SERIAL_NUM_BITS as usize) as u32;
         let _unknown = br.read_uvarint32();
 
         let class_info = unsafe { entity_classes.by_id_unckecked(class_id) };
         let serializer =
             unsafe { serializers.by_name_hash_unckecked(class_info.network_name_hash) };
+        self.class_ids
+            .insert(class_info.network_name_hash, class_id);
 
         let mut entity = match self.baseline_entities.entry(class_id) {
             Entry::Occupied(oe) => {
                 let mut entity = oe.get().clone();
                 entity.index = index;
+                entity.serial = serial;
+                entity.created_from = created_from;
                 entity
             }
             Entry::Vacant(ve) => {
                 let mut entity = Entity {
                     index,
+                    serial: 0,
+                    class_id,
+                    created_tick: -1,
+                    updated_tick: -1,
+                    update_count: 0,
+                    created_from,
                     fields: HashMap::with_capacity_and_hasher(
                         serializer.fields.len(),
                         BuildHasherDefault::default(),
@@ -418,13 +867,20 @@ impl EntityContainer {
                 entity.parse(field_decode_ctx, &mut baseline_br, &mut self.field_paths)?;
                 baseline_br.is_overflowed()?;
 
-                ve.insert(entity).clone()
+                let mut entity = ve.insert(entity).clone();
+                entity.serial = serial;
+                entity
             }
         };
+        entity.created_tick = tick;
+        entity.updated_tick = tick;
 
         entity.parse(field_decode_ctx, br, &mut self.field_paths)?;
 
-        self.entities.insert(index, entity);
+        if let Some(prev) = self.entities.insert(index, entity) {
+            self.remove_from_class_index(&prev);
+        }
+        self.add_to_class_index(index, class_id);
         // SAFETY: the entity was just inserted ^, it's safe.
         Ok(unsafe { self.entities.get(&index).unwrap_unchecked() })
     }
@@ -440,7 +896,24 @@ impl EntityContainer {
             "tried to delete non-existent entity #{index}"
         );
 
-        entity.unwrap_unchecked()
+        let entity = entity.unwrap_unchecked();
+        self.remove_from_class_index(&entity);
+        entity
+    }
+
+    fn add_to_class_index(&mut self, index: i32, class_id: i32) {
+        let indices = self.by_class.entry(class_id).or_default();
+        if let Err(pos) = indices.binary_search(&index) {
+            indices.insert(pos, index);
+        }
+    }
+
+    fn remove_from_class_index(&mut self, entity: &Entity) {
+        if let Some(indices) = self.by_class.get_mut(&entity.class_id) {
+            if let Ok(pos) = indices.binary_search(&entity.index) {
+                indices.remove(pos);
+            }
+        }
     }
 
     // SAFETY: if entity was ever created, and not deleted, it can be updated!
@@ -449,6 +922,7 @@ impl EntityContainer {
     pub(crate) unsafe fn handle_update_unchecked(
         &mut self,
         index: i32,
+        tick: i32,
         field_decode_ctx: &mut FieldDecodeContext,
         br: &mut BitReader,
     ) -> Result<&Entity, BitReaderOverflowError> {
@@ -461,6 +935,8 @@ impl EntityContainer {
 
         let entity = entity.unwrap_unchecked();
         entity.parse(field_decode_ctx, br, &mut self.field_paths)?;
+        entity.updated_tick = tick;
+        entity.update_count += 1;
         Ok(entity)
     }
 
@@ -475,6 +951,53 @@ impl EntityContainer {
         self.entities.get(index)
     }
 
+    /// resolves networked handle. unlike `get(&ehandle_to_index(handle))` this validates serial
+    /// number, thus a stale handle that points at an index that was reused by another entity
+    /// resolves to `None`.
+    pub fn get_by_handle(&self, handle: u32) -> Option<&Entity> {
+        if !is_ehandle_valid(handle) {
+            return None;
+        }
+        self.entities
+            .get(&ehandle_to_index(handle))
+            .filter(|entity| {
+                entity.serial & NETWORKED_EHANDLE_SERIAL_NUMBER_MASK == ehandle_to_serial(handle)
+            })
+    }
+
+    /// iterates over entities of the given class in order of their indices. unlike filtering
+    /// [`EntityContainer::iter`] this does not scan all entities.
+    pub fn iter_by_class_id(&self, class_id: i32) -> impl Iterator<Item = &Entity> {
+        self.by_class
+            .get(&class_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|index| self.entities.get(index))
+    }
+
+    /// same as [`EntityContainer::iter_by_class_id`], but takes serializer name hash (see
+    /// [`Entity::serializer_name_heq`]).
+    pub fn iter_by_class(&self, serializer_name_hash: u64) -> impl Iterator<Item = &Entity> {
+        self.class_ids
+            .get(&serializer_name_hash)
+            .into_iter()
+            .flat_map(|&class_id| self.iter_by_class_id(class_id))
+    }
+
+    /// finds entity with the lowest index of the given class; handy for singletons such as game
+    /// rules proxy.
+    pub fn find_first_by_class(&self, serializer_name_hash: u64) -> Option<&Entity> {
+        self.iter_by_class(serializer_name_hash).next()
+    }
+
+    /// drops cached baseline entities of the given classes; they'll be re-decoded from
+    /// instancebaseline next time an entity of such class is created.
+    pub(crate) fn invalidate_baselines(&mut self, class_ids: impl Iterator<Item = i32>) {
+        for class_id in class_ids {
+            self.baseline_entities.remove(&class_id);
+        }
+    }
+
     pub fn iter_baselines(&self) -> impl Iterator<Item = (&i32, &Entity)> {
         self.baseline_entities.iter()
     }
@@ -488,9 +1011,403 @@ impl EntityContainer {
     pub fn clear(&mut self) {
         self.entities.clear();
         self.baseline_entities.clear();
+        self.by_class.clear();
+        self.class_ids.clear();
     }
 
     pub fn is_empty(&self) -> bool {
         self.entities.is_empty()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::demofile::DemoFile;
+    use crate::fieldkey::FieldKey;
+    use crate::parser::{Context, NopVisitor, Parser};
+    use crate::testdemo::{
+        BODY, BODY_CELL_X, BODY_CELL_Y, HEALTH, TEST_ENTITY_CLASS_ID, TEST_GAME_RULES_CLASS_ID,
+        TestDemoBuilder, VALUES,
+    };
+
+    type TestParser = Parser<DemoFile<Cursor<Vec<u8>>>, NopVisitor>;
+
+    /// finishes the demo at `end_tick` and sets up a parser for it. parser is not run: the
+    /// caller decides whether to [`Parser::run_to_end`] (which ignores full packets) or to
+    /// [`Parser::run_to_tick`].
+    fn parse_entities(
+        builder: TestDemoBuilder,
+        end_tick: i32,
+    ) -> Result<TestParser, anyhow::Error> {
+        let data = builder.finish(end_tick)?;
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        Ok(Parser::from_stream(demo_file)?)
+    }
+
+    #[test]
+    fn test_ehandle() {
+        let handle = ehandle_from_parts(42, 0b1_0000_0000_0111);
+        assert_eq!(ehandle_to_index(handle), 42);
+        // NOTE: serial is truncated to networked bits
+        assert_eq!(ehandle_to_serial(handle), 0b00_0000_0111);
+        assert!(is_ehandle_valid(handle));
+        assert!(!is_ehandle_valid(INVALID_NETWORKED_EHANDLE_VALUE));
+    }
+
+    #[test]
+    fn test_get_by_handle() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .create_entity(42, TEST_ENTITY_CLASS_ID, 7, &[])
+            .write_packet(10)?;
+        let mut parser = parse_entities(builder, 10)?;
+        parser.run_to_end()?;
+        let Some(entities) = parser.context().entities() else {
+            anyhow::bail!("no entities");
+        };
+
+        let handle = ehandle_from_parts(42, 7);
+        assert_eq!(
+            entities.get(&42).map(|entity| entity.ehandle()),
+            Some(handle)
+        );
+        assert_eq!(
+            entities.get_by_handle(handle).map(|entity| entity.index()),
+            Some(42)
+        );
+        // stale handle, index was reused
+        assert!(entities.get_by_handle(ehandle_from_parts(42, 6)).is_none());
+        assert!(entities.get_by_handle(ehandle_from_parts(43, 7)).is_none());
+        assert!(
+            entities
+                .get_by_handle(INVALID_NETWORKED_EHANDLE_VALUE)
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_by_class() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .create_entity(5, TEST_ENTITY_CLASS_ID, 1, &[])
+            .create_entity(3, TEST_ENTITY_CLASS_ID, 1, &[])
+            .create_entity(4, TEST_GAME_RULES_CLASS_ID, 1, &[])
+            .write_full_packet(10)?
+            .delete_entity(3)
+            .write_packet(40)?;
+        let mut parser = parse_entities(builder, 40)?;
+        let test_entity = fxhash::hash_bytes(b"CTestEntity");
+        let indices = |ctx: &Context| -> Option<Vec<i32>> {
+            Some(
+                ctx.entities()?
+                    .iter_by_class(test_entity)
+                    .map(|entity| entity.index())
+                    .collect(),
+            )
+        };
+        let first = |ctx: &Context, serializer_name_hash: u64| {
+            ctx.entities()?
+                .find_first_by_class(serializer_name_hash)
+                .map(|entity| entity.index())
+        };
+
+        parser.run_to_tick(10)?;
+        let ctx = parser.context();
+        assert_eq!(indices(ctx), Some(vec![3, 5]));
+        assert_eq!(first(ctx, test_entity), Some(3));
+        assert_eq!(
+            ctx.entities()
+                .map(|entities| entities.iter_by_class_id(TEST_GAME_RULES_CLASS_ID).count()),
+            Some(1)
+        );
+        assert_eq!(first(ctx, fxhash::hash_bytes(b"CTestGameRules")), Some(4));
+        assert!(first(ctx, fxhash::hash_bytes(b"CNotNetworked")).is_none());
+
+        parser.run_to_end()?;
+        assert_eq!(indices(parser.context()), Some(vec![5]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_and_update_bookkeeping() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .create_entity(1, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(10)?
+            .update_entity(1, &[(HEALTH, FieldValue::I64(1))])
+            .write_packet(40)?
+            .update_entity(1, &[(HEALTH, FieldValue::I64(2))])
+            .write_packet(70)?
+            .write_full_packet(1810)?
+            .update_entity(1, &[(HEALTH, FieldValue::I64(3))])
+            .write_packet(1840)?;
+        let mut parser = parse_entities(builder, 1840)?;
+        let entity = |parser: &TestParser| {
+            parser
+                .context()
+                .entities()
+                .and_then(|entities| entities.get(&1))
+                .map(|entity| {
+                    (
+                        entity.created_from(),
+                        entity.created_tick(),
+                        entity.updated_tick(),
+                        entity.update_count(),
+                    )
+                })
+        };
+
+        // NOTE: run_to_end ignores full packets.
+        parser.run_to_end()?;
+        assert_eq!(
+            entity(&parser),
+            Some((EntityCreatedFrom::Delta, 10, 1840, 3))
+        );
+
+        // entity that existed before the full packet is re-created by it
+        parser.run_to_tick(1810)?;
+        assert_eq!(
+            entity(&parser),
+            Some((EntityCreatedFrom::FullPacket, 1810, 1810, 0))
+        );
+        parser.run_to_tick(1840)?;
+        assert_eq!(
+            entity(&parser),
+            Some((EntityCreatedFrom::FullPacket, 1810, 1840, 1))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalidate_baselines() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(10))])
+            .create_entity(1, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(10)?
+            .create_entity(2, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(40)?
+            // baseline of the class changed; entities created after this must be decoded from
+            // new bytes
+            .update_baseline(TEST_ENTITY_CLASS_ID, &[(HEALTH, FieldValue::I64(20))])
+            .create_entity(3, TEST_ENTITY_CLASS_ID, 1, &[])
+            .write_packet(70)?;
+        let mut parser = parse_entities(builder, 70)?;
+        parser.run_to_end()?;
+
+        let health_key = FieldKey::new("m_iHealth").to_u64();
+        let Some(entities) = parser.context().entities() else {
+            anyhow::bail!("no entities");
+        };
+        let health = |entity: Option<&Entity>| entity?.get_value::<i64>(&health_key);
+        assert_eq!(health(entities.get(&1)), Some(10));
+        assert_eq!(health(entities.get(&2)), Some(10));
+        assert_eq!(health(entities.get(&3)), Some(20));
+        assert_eq!(
+            health(entities.get_baseline(&TEST_ENTITY_CLASS_ID)),
+            Some(20)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_shrink() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .create_entity(
+                1,
+                TEST_ENTITY_CLASS_ID,
+                1,
+                &[
+                    (VALUES, FieldValue::U64(3)),
+                    (&[2, 0], FieldValue::U64(10)),
+                    (&[2, 1], FieldValue::U64(11)),
+                    (&[2, 2], FieldValue::U64(12)),
+                ],
+            )
+            .write_packet(10)?
+            // shrink from 3 to 1
+            .update_entity(1, &[(VALUES, FieldValue::U64(1))])
+            .write_packet(40)?;
+        let mut parser = parse_entities(builder, 40)?;
+        parser.run_to_end()?;
+        let Some(entity) = parser
+            .context()
+            .entities()
+            .and_then(|entities| entities.get(&1))
+        else {
+            anyhow::bail!("no entity");
+        };
+
+        let array_key = FieldKey::new("m_vecValues");
+        assert_eq!(entity.array_len(&array_key.to_u64()), Some(1));
+        assert_eq!(entity.iter().count(), 2);
+        let values = entity
+            .array_element_keys(&array_key.to_u64())
+            .filter_map(|element_key| entity.get_value::<u64>(&element_key))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [10]);
+        let stale_key = array_key.index(2).to_u64();
+        assert!(entity.get_value::<u64>(&stale_key).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed_array_keys() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .create_entity(
+                1,
+                TEST_ENTITY_CLASS_ID,
+                1,
+                &[
+                    (&[4, 0], FieldValue::U64(10)),
+                    (&[4, 1], FieldValue::U64(11)),
+                    (&[4, 2], FieldValue::U64(12)),
+                ],
+            )
+            .write_packet(10)?;
+        let mut parser = parse_entities(builder, 10)?;
+        parser.run_to_end()?;
+        let Some(entity) = parser
+            .context()
+            .entities()
+            .and_then(|entities| entities.get(&1))
+        else {
+            anyhow::bail!("no entity");
+        };
+
+        let array_key = FieldKey::new("m_nScores");
+        let values = (0..3)
+            .map(|i| entity.get_value::<u64>(&array_key.index(i).to_u64()))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [Some(10), Some(11), Some(12)]);
+        // elements used to be keyed by the var name that they share with the array
+        let old_key = array_key.name("m_nScores").to_u64();
+        assert!(entity.get_value::<u64>(&old_key).is_none());
+        assert_eq!(entity.iter().count(), 3);
+        assert_eq!(
+            entity.get_path_name(&array_key.index(2).to_u64(), parser.context().symbols()),
+            Some("m_nScores.2".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pointer_reset() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .create_entity(
+                1,
+                TEST_ENTITY_CLASS_ID,
+                1,
+                &[
+                    (HEALTH, FieldValue::I64(100)),
+                    (BODY, FieldValue::Bool(true)),
+                    (BODY_CELL_X, FieldValue::U64(32)),
+                    (BODY_CELL_Y, FieldValue::U64(4)),
+                ],
+            )
+            .write_full_packet(10)?
+            .update_entity(1, &[(BODY, FieldValue::Bool(false))])
+            .write_packet(40)?;
+        let mut parser = parse_entities(builder, 40)?;
+        let key = FieldKey::new("CBodyComponent");
+        let cell_x_key = key.name("m_nCellX").to_u64();
+        let entity = |parser: &TestParser| {
+            parser
+                .context()
+                .entities()
+                .and_then(|entities| entities.get(&1))
+                .map(|entity| {
+                    (
+                        entity.has_component(&key.to_u64()),
+                        entity.get_value::<u64>(&cell_x_key),
+                        entity.iter().count(),
+                    )
+                })
+        };
+
+        parser.run_to_tick(10)?;
+        assert_eq!(entity(&parser), Some((true, Some(32), 4)));
+
+        parser.run_to_end()?;
+        // fields that are not beneath the pointer are untouched
+        assert_eq!(entity(&parser), Some((false, None, 2)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree() -> Result<(), anyhow::Error> {
+        let mut builder = TestDemoBuilder::new()?;
+        builder
+            .create_entity(
+                1,
+                TEST_ENTITY_CLASS_ID,
+                1,
+                &[
+                    (VALUES, FieldValue::U64(2)),
+                    (&[2, 1], FieldValue::U64(7)),
+                    (BODY, FieldValue::Bool(true)),
+                    (BODY_CELL_X, FieldValue::U64(32)),
+                    (&[4, 0], FieldValue::U64(10)),
+                    (&[4, 2], FieldValue::U64(12)),
+                ],
+            )
+            .write_packet(10)?;
+        let mut parser = parse_entities(builder, 10)?;
+        parser.run_to_end()?;
+        let Some(entity) = parser
+            .context()
+            .entities()
+            .and_then(|entities| entities.get(&1))
+        else {
+            anyhow::bail!("no entity");
+        };
+
+        let tree = entity.tree();
+        let hash = |name: &str| fxhash::hash_bytes(name.as_bytes());
+        let values = tree.get(hash("m_vecValues"));
+        assert!(matches!(values, Some(EntityNode::Array(elements)) if elements.len() == 1));
+        assert!(matches!(
+            values
+                .and_then(|node| node.index(1))
+                .and_then(EntityNode::as_value),
+            Some(FieldValue::U64(7))
+        ));
+        assert!(matches!(
+            tree.get(hash("CBodyComponent"))
+                .and_then(|node| node.get(hash("m_nCellX")))
+                .and_then(EntityNode::as_value),
+            Some(FieldValue::U64(32))
+        ));
+        let scores = tree.get(hash("m_nScores"));
+        assert!(matches!(scores, Some(EntityNode::Array(elements)) if elements.len() == 2));
+        assert!(matches!(
+            scores
+                .and_then(|node| node.index(2))
+                .and_then(EntityNode::as_value),
+            Some(FieldValue::U64(12))
+        ));
+        // fields that have no value are not in the tree
+        assert!(tree.get(hash("m_iHealth")).is_none());
+        assert!(
+            tree.get(hash("CBodyComponent"))
+                .and_then(|node| node.get(hash("m_nCellY")))
+                .is_none()
+        );
+
+        Ok(())
+    }
+}
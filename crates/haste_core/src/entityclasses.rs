@@ -1,20 +1,32 @@
+use std::hash::BuildHasherDefault;
+
+use hashbrown::HashMap;
+use nohash::NoHashHasher;
 use valveprotos::common::CDemoClassInfo;
 
 use crate::fxhash;
+use crate::symboltable::{Symbol, SymbolTable};
 
 #[derive(Clone)]
 pub struct ClassInfo {
+    pub class_id: i32,
     pub network_name_hash: u64,
+    /// resolve with [`crate::parser::Context::symbols`].
+    pub network_name: Symbol,
 }
 
 pub struct EntityClasses {
     pub classes: usize,
     pub bits: usize,
     class_infos: Vec<ClassInfo>,
+    // NOTE: serializers are named after network names of classes (see
+    // EntityContainer::handle_create), thus this also maps serializer name hashes to class ids.
+    class_ids: HashMap<u64, i32, BuildHasherDefault<NoHashHasher<u64>>>,
 }
 
 impl EntityClasses {
-    pub fn parse(cmd: CDemoClassInfo) -> Self {
+    /// network names are being interned into the given symbol table.
+    pub fn parse(cmd: CDemoClassInfo, symbol_table: &mut SymbolTable) -> Self {
         let class_count = cmd.classes.len();
 
         // bits is the number of bits to read for entity classes. stolen from
@@ -28,16 +40,25 @@ impl EntityClasses {
             .map(|(i, class)| {
                 let class_id = class.class_id() as usize;
                 assert_eq!(class_id, i, "invliad class id");
+                let network_name = symbol_table.intern(class.network_name());
                 ClassInfo {
-                    network_name_hash: fxhash::hash_bytes(class.network_name().as_bytes()),
+                    class_id: class_id as i32,
+                    network_name_hash: network_name.hash,
+                    network_name,
                 }
             })
             .collect();
 
+        let class_ids = class_infos
+            .iter()
+            .map(|class_info| (class_info.network_name_hash, class_info.class_id))
+            .collect();
+
         Self {
             classes: class_count,
             bits,
             class_infos,
+            class_ids,
         }
     }
 
@@ -45,4 +66,83 @@ impl EntityClasses {
     pub unsafe fn by_id_unckecked(&self, class_id: i32) -> &ClassInfo {
         self.class_infos.get_unchecked(class_id as usize)
     }
+
+    #[inline]
+    pub fn by_id(&self, class_id: i32) -> Option<&ClassInfo> {
+        usize::try_from(class_id)
+            .ok()
+            .and_then(|class_id| self.class_infos.get(class_id))
+    }
+
+    #[inline]
+    pub fn by_name_hash(&self, network_name_hash: u64) -> Option<&ClassInfo> {
+        self.class_id_by_serializer_name_hash(network_name_hash)
+            .and_then(|class_id| self.by_id(class_id))
+    }
+
+    /// looks up class by its network name (for example `CCitadelPlayerPawn`).
+    #[inline]
+    pub fn by_name(&self, network_name: &str) -> Option<&ClassInfo> {
+        self.by_name_hash(fxhash::hash_bytes(network_name.as_bytes()))
+    }
+
+    /// maps serializer name hash (see
+    /// [`crate::flattenedserializers::FlattenedSerializer::serializer_name`]) to class id.
+    /// serializer name is the same thing as class' network name.
+    #[inline]
+    pub fn class_id_by_serializer_name_hash(&self, serializer_name_hash: u64) -> Option<i32> {
+        self.class_ids.get(&serializer_name_hash).copied()
+    }
+
+    /// iterates over all classes in order of their ids.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &ClassInfo> {
+        self.class_infos.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use valveprotos::common::c_demo_class_info::ClassT;
+
+    use super::*;
+
+    #[test]
+    fn test_lookups() {
+        let cmd = CDemoClassInfo {
+            classes: ["CCitadelGameRulesProxy", "CCitadelPlayerPawn"]
+                .iter()
+                .enumerate()
+                .map(|(class_id, network_name)| ClassT {
+                    class_id: Some(class_id as i32),
+                    network_name: Some(network_name.to_string()),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let mut symbols = SymbolTable::default();
+        let entity_classes = EntityClasses::parse(cmd, &mut symbols);
+
+        let pawn = entity_classes.by_name("CCitadelPlayerPawn");
+        assert_eq!(pawn.map(|class_info| class_info.class_id), Some(1));
+        assert_eq!(
+            pawn.map(|class_info| symbols.resolve(class_info.network_name)),
+            Some("CCitadelPlayerPawn")
+        );
+        assert_eq!(
+            entity_classes
+                .class_id_by_serializer_name_hash(fxhash::hash_bytes(b"CCitadelGameRulesProxy")),
+            Some(0)
+        );
+        assert!(entity_classes.by_name("CNPC_MidBoss").is_none());
+        assert!(entity_classes.by_id(2).is_none());
+        assert!(entity_classes.by_id(-1).is_none());
+        assert_eq!(
+            entity_classes
+                .iter()
+                .map(|class_info| class_info.class_id)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+    }
 }
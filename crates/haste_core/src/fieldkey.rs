@@ -0,0 +1,268 @@
+//! field keys are what [`crate::entities::Entity`] stores field values by. a key is a hash of
+//! field's path, for example `m_vecPlayerData.3.m_iszPlayerName`:
+//! - first segment seeds the hash;
+//! - names are hashed with [`fxhash::hash_bytes`] and added to the hash;
+//! - array indices (both dynamic and fixed arrays) are hashed as numbers (not as strings) and added
+//!   to the hash.
+//!
+//! NOTE: elements of fixed arrays used to be keyed by the var name that they share with the array
+//! (for example `m_hItems.m_hItems`), thus all of them ended up under a single key and only the
+//! last written one survived. keys that were built that way no longer resolve; use indices
+//! instead (for example `m_hItems.3`).
+//!
+//! keys can be built in const context (see [`fkey_from_path`] and [`fkey_from_parts`]) or at
+//! runtime (see [`FieldKey`]).
+
+use crate::fxhash;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ParseFieldKeyError {
+    #[error("path is empty")]
+    EmptyPath,
+    #[error("segment {0} is empty")]
+    EmptySegment(usize),
+    #[error("path must begin with a name")]
+    LeadingIndex,
+}
+
+/// hash of an array index. this is what the parser adds to the hash for each array element.
+#[inline(always)]
+pub(crate) const fn index_hash(index: u64) -> u64 {
+    // NOTE: it's sort of weird to hash index, yup. but it simplifies things when "user" builds a
+    // key that has numbers / it makes it so that there's no need to check whether part of a key
+    // needs to be hashed or not - just hash all parts.
+    fxhash::add_u64_to_hash(0, index)
+}
+
+/// returns `Some(index)` if segment consists only of ascii digits.
+const fn parse_index(segment: &[u8]) -> Option<u64> {
+    if segment.is_empty() {
+        return None;
+    }
+
+    let mut index: u64 = 0;
+    let mut i = 0;
+    while i < segment.len() {
+        let b = segment[i];
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        index = match index.checked_mul(10) {
+            Some(index) => match index.checked_add((b - b'0') as u64) {
+                Some(index) => index,
+                None => return None,
+            },
+            None => return None,
+        };
+        i += 1;
+    }
+
+    Some(index)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKeyPart<'a> {
+    Name(&'a str),
+    Index(usize),
+}
+
+/// generates field key from given parts. can and recommended to be called from a const context.
+///
+/// ```ignore
+/// const KEY: u64 = fkey_from_parts(&[
+///     FieldKeyPart::Name("m_vecPlayerData"),
+///     FieldKeyPart::Index(3),
+///     FieldKeyPart::Name("m_iszPlayerName"),
+/// ]);
+/// ```
+pub const fn fkey_from_parts(parts: &[FieldKeyPart]) -> u64 {
+    assert!(!parts.is_empty(), "invalid path");
+    assert!(
+        matches!(parts[0], FieldKeyPart::Name(_)),
+        "path must begin with a name"
+    );
+
+    let mut key = match parts[0] {
+        FieldKeyPart::Name(name) => FieldKey::new(name),
+        // NOTE: unreachable because of the assert above.
+        FieldKeyPart::Index(index) => FieldKey(index_hash(index as u64)),
+    };
+
+    let mut i = 1;
+    while i < parts.len() {
+        key = match parts[i] {
+            FieldKeyPart::Name(name) => key.name(name),
+            FieldKeyPart::Index(index) => key.index(index),
+        };
+        i += 1;
+    }
+
+    key.to_u64()
+}
+
+/// generates field key from given path. can and recommended to be called from a const context.
+/// when called from a const context, the function is interpreted by the compiler at compile time
+/// meaning that there's no const of generating key for given path at runtime.
+///
+/// segments that consist only of digits are treated as array indices, for example
+/// `fkey_from_path(&["m_vecPlayerData", "3", "m_iszPlayerName"])`.
+pub const fn fkey_from_path(path: &[&str]) -> u64 {
+    assert!(!path.is_empty(), "invalid path");
+
+    let mut key = FieldKey::new(path[0]);
+
+    let mut i = 1;
+    while i < path.len() {
+        key = key.segment(path[i]);
+        i += 1;
+    }
+
+    key.to_u64()
+}
+
+/// field key builder.
+///
+/// ```ignore
+/// let key = FieldKey::new("m_vecPlayerData").index(3).name("m_iszPlayerName");
+/// assert_eq!(key, FieldKey::parse("m_vecPlayerData.3.m_iszPlayerName")?);
+/// entity.get_value::<String>(&key.to_u64());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldKey(u64);
+
+impl FieldKey {
+    /// begins a key with the top-level field name.
+    #[inline]
+    pub const fn new(name: &str) -> Self {
+        Self(fxhash::hash_bytes(name.as_bytes()))
+    }
+
+    #[inline]
+    pub const fn name(self, name: &str) -> Self {
+        Self(fxhash::add_u64_to_hash(
+            self.0,
+            fxhash::hash_bytes(name.as_bytes()),
+        ))
+    }
+
+    #[inline]
+    pub const fn index(self, index: usize) -> Self {
+        Self(fxhash::add_u64_to_hash(self.0, index_hash(index as u64)))
+    }
+
+    /// appends either an index (if segment consists only of digits) or a name.
+    #[inline]
+    pub const fn segment(self, segment: &str) -> Self {
+        match parse_index(segment.as_bytes()) {
+            Some(index) => Self(fxhash::add_u64_to_hash(self.0, index_hash(index))),
+            None => self.name(segment),
+        }
+    }
+
+    /// parses dot-separated path, for example `m_vecPlayerData.3.m_iszPlayerName`.
+    pub fn parse(path: &str) -> Result<Self, ParseFieldKeyError> {
+        if path.is_empty() {
+            return Err(ParseFieldKeyError::EmptyPath);
+        }
+
+        let mut segments = path.split('.').enumerate();
+        let mut key = match segments.next() {
+            Some((_, "")) => return Err(ParseFieldKeyError::EmptySegment(0)),
+            Some((_, first)) if parse_index(first.as_bytes()).is_some() => {
+                return Err(ParseFieldKeyError::LeadingIndex);
+            }
+            Some((_, first)) => Self::new(first),
+            None => return Err(ParseFieldKeyError::EmptyPath),
+        };
+
+        for (i, segment) in segments {
+            if segment.is_empty() {
+                return Err(ParseFieldKeyError::EmptySegment(i));
+            }
+            key = key.segment(segment);
+        }
+
+        Ok(key)
+    }
+
+    #[inline]
+    pub const fn to_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl From<FieldKey> for u64 {
+    #[inline]
+    fn from(value: FieldKey) -> Self {
+        value.0
+    }
+}
+
+impl std::str::FromStr for FieldKey {
+    type Err = ParseFieldKeyError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_field_key() -> Result<(), ParseFieldKeyError> {
+        const FROM_PARTS: u64 = fkey_from_parts(&[
+            FieldKeyPart::Name("m_vecPlayerData"),
+            FieldKeyPart::Index(3),
+            FieldKeyPart::Name("m_iszPlayerName"),
+        ]);
+        const FROM_PATH: u64 = fkey_from_path(&["m_vecPlayerData", "3", "m_iszPlayerName"]);
+
+        let built = FieldKey::new("m_vecPlayerData")
+            .index(3)
+            .name("m_iszPlayerName");
+        let parsed = FieldKey::parse("m_vecPlayerData.3.m_iszPlayerName")?;
+        assert_eq!(built, parsed);
+        assert_eq!(built.to_u64(), FROM_PARTS);
+        assert_eq!(built.to_u64(), FROM_PATH);
+
+        // this is how Entity::parse builds keys
+        let by_parser = fxhash::add_u64_to_hash(
+            fxhash::add_u64_to_hash(
+                fxhash::hash_bytes(b"m_vecPlayerData"),
+                fxhash::add_u64_to_hash(0, 3),
+            ),
+            fxhash::hash_bytes(b"m_iszPlayerName"),
+        );
+        assert_eq!(built.to_u64(), by_parser);
+
+        assert_ne!(
+            FieldKey::parse("m_vecPlayerData.3")?,
+            FieldKey::parse("m_vecPlayerData.4")?
+        );
+        // elements of fixed arrays are addressed by indices too
+        assert_ne!(
+            FieldKey::parse("m_hItems.3")?.to_u64(),
+            FieldKey::parse("m_hItems.m_hItems")?.to_u64()
+        );
+        // single segment keys did not change
+        assert_eq!(
+            FieldKey::parse("m_lifeState")?.to_u64(),
+            fxhash::hash_bytes(b"m_lifeState")
+        );
+
+        assert_eq!(FieldKey::parse(""), Err(ParseFieldKeyError::EmptyPath));
+        assert_eq!(
+            FieldKey::parse("m_vecPlayerData..m_iszPlayerName"),
+            Err(ParseFieldKeyError::EmptySegment(1))
+        );
+        assert_eq!(
+            FieldKey::parse("3.m_iszPlayerName"),
+            Err(ParseFieldKeyError::LeadingIndex)
+        );
+
+        Ok(())
+    }
+}
@@ -60,14 +60,18 @@ pub(crate) enum FieldSpecialDescriptor {
     /// ```
     DynamicSerializerArray,
 
-    // TODO: make use of the poiter special type (atm it's useless; but it's
-    // supposed to be used to determine whether a new "entity" must be created
-    // (and deserialized value of the pointer field (/bool) must not be
-    // stored)).
+    /// value of the pointer field is a bool that tells whether the object it points to exists;
+    /// when it becomes false, fields of the object are being dropped (see
+    /// [`crate::entities::Entity::has_component`]).
     Pointer,
 }
 
 impl FieldSpecialDescriptor {
+    #[inline(always)]
+    pub(crate) fn is_fixed_array(&self) -> bool {
+        matches!(self, Self::FixedArray { .. })
+    }
+
     #[inline(always)]
     pub(crate) fn is_dynamic_array(&self) -> bool {
         matches!(
@@ -75,6 +79,11 @@ impl FieldSpecialDescriptor {
             Self::DynamicArray { .. } | Self::DynamicSerializerArray
         )
     }
+
+    #[inline(always)]
+    pub(crate) fn is_pointer(&self) -> bool {
+        matches!(self, Self::Pointer)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -273,8 +282,8 @@ fn visit_any(
 
 pub(crate) fn get_field_metadata(
     field: &FlattenedSerializerField,
-    var_type: &String,
+    var_type: &str,
 ) -> Result<FieldMetadata, FieldMetadataError> {
-    let expr = vartype::parse(var_type.as_str())?;
+    let expr = vartype::parse(var_type)?;
     visit_any(expr, field)
 }
@@ -5,6 +5,8 @@ use std::fmt::Debug;
 use lazy_static::lazy_static;
 
 use crate::bitreader::BitReader;
+#[cfg(any(test, feature = "test-util"))]
+use crate::bitwriter::BitWriter;
 
 // NOTE: credit for figuring out field path encoding goes to invokr (github.com/dotabuff/manta) and
 // spheenik (github.com/skadistats/clarity).
@@ -681,3 +683,104 @@ pub(crate) fn read_field_paths(br: &mut BitReader, fps: &mut [FieldPath]) -> usi
         };
     }
 }
+
+// NOTE: index of the descriptor in FIELDOP_DESCRIPTORS equals num of the leaf in FIELDOP_HIERARCHY.
+#[cfg(any(test, feature = "test-util"))]
+const FIELDOP_PUSH_N_AND_NON_TOPOGRAPHICAL: usize = 26;
+#[cfg(any(test, feature = "test-util"))]
+const FIELDOP_POP_N_AND_NON_TOPOGRAPHICAL: usize = 35;
+#[cfg(any(test, feature = "test-util"))]
+const FIELDOP_FIELD_PATH_ENCODE_FINISH: usize = 39;
+
+#[cfg(any(test, feature = "test-util"))]
+fn write_fieldop(bw: &mut BitWriter, num: usize) {
+    fn find(node: &Node<FieldOp>, num: usize, code: &mut Vec<bool>) -> bool {
+        match node {
+            Node::Leaf { num: leaf_num, .. } => *leaf_num == num,
+            Node::Branch { left, right, .. } => {
+                for (bit, next) in [(false, left), (true, right)] {
+                    code.push(bit);
+                    if find(next, num, code) {
+                        return true;
+                    }
+                    code.pop();
+                }
+                false
+            }
+        }
+    }
+
+    let mut code = Vec::new();
+    if !find(&FIELDOP_HIERARCHY, num, &mut code) {
+        unreachable!("unknown field op {num}");
+    }
+    code.into_iter().for_each(|bit| bw.write_bool(bit));
+}
+
+/// inverse of [`read_field_paths`]; each path is a list of components (see [`FieldPath::iter`]).
+///
+/// NOTE: this is not what valve's encoder does (it picks the cheapest op), only "non
+/// topographical" push and pop ops are used; good enough for producing test replays.
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn write_field_paths(bw: &mut BitWriter, paths: &[&[u8]]) {
+    let mut fp = FieldPath::default();
+
+    for path in paths {
+        debug_assert!(!path.is_empty() && path.len() <= fp.data.len());
+        let last = path.len() - 1;
+
+        if last >= fp.last {
+            write_fieldop(bw, FIELDOP_PUSH_N_AND_NON_TOPOGRAPHICAL);
+            for (i, &component) in path.iter().enumerate().take(fp.last + 1) {
+                let delta = component as i32 - fp.data[i] as i32;
+                bw.write_bool(delta != 0);
+                if delta != 0 {
+                    bw.write_varint32(delta - 1);
+                }
+            }
+            bw.write_ubitvar((last - fp.last) as u32);
+            path[fp.last + 1..]
+                .iter()
+                .for_each(|&component| bw.write_ubitvarfp(component as u32));
+        } else {
+            write_fieldop(bw, FIELDOP_POP_N_AND_NON_TOPOGRAPHICAL);
+            bw.write_ubitvarfp((fp.last - last) as u32);
+            for (i, &component) in path.iter().enumerate() {
+                let delta = component as i32 - fp.data[i] as i32;
+                bw.write_bool(delta != 0);
+                if delta != 0 {
+                    bw.write_varint32(delta);
+                }
+            }
+        }
+
+        fp.data = [0; 7];
+        fp.data[..path.len()].copy_from_slice(path);
+        fp.last = last;
+    }
+
+    write_fieldop(bw, FIELDOP_FIELD_PATH_ENCODE_FINISH);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitreader::BitReaderOverflowError;
+
+    #[test]
+    fn test_write_and_read_field_paths() -> Result<(), BitReaderOverflowError> {
+        let paths: [&[u8]; 7] = [&[0], &[1], &[3, 0], &[3, 2, 1], &[4], &[4, 0, 255], &[200]];
+
+        let mut bw = BitWriter::new();
+        write_field_paths(&mut bw, &paths);
+        let buf = bw.into_inner();
+
+        let mut br = BitReader::new(&buf);
+        let mut fps = vec![FieldPath::default(); paths.len()];
+        assert_eq!(read_field_paths(&mut br, &mut fps), paths.len());
+        for (fp, path) in fps.iter().zip(paths) {
+            assert_eq!(fp.iter().copied().collect::<Vec<u8>>(), path);
+        }
+        br.is_overflowed()
+    }
+}
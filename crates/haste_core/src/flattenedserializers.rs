@@ -14,7 +14,8 @@ use valveprotos::prost::{self, Message};
 use crate::fieldmetadata::{
     get_field_metadata, FieldMetadata, FieldMetadataError, FieldSpecialDescriptor,
 };
-use crate::fxhash;
+pub use crate::symboltable::Symbol;
+use crate::symboltable::SymbolTable;
 
 #[derive(thiserror::Error, Debug)]
 pub enum FlattenedSerializersError {
@@ -26,41 +27,10 @@ pub enum FlattenedSerializersError {
     FieldMetadataError(#[from] FieldMetadataError),
 }
 
-// TODO: symbol table / string cache (but do not use servo's string cache
-// because it's super slow; it relies on rust-phf that uses sip13 cryptograpgic
-// hasher, and you can't replace it with something else (without forking it
-// really)).
-//
-// valve's implementation: public/tier1/utlsymbol.h;
-// blukai's 3head implementation:
-#[derive(Debug, Clone, Default)]
-pub struct Symbol {
-    pub hash: u64,
-    // TODO: consider renaming str into boxed_str
-    #[cfg(feature = "preserve-metadata")]
-    pub str: Box<str>,
-}
-
-impl From<&String> for Symbol {
-    #[inline(always)]
-    fn from(value: &String) -> Self {
-        Self {
-            hash: fxhash::hash_bytes(value.as_bytes()),
-            #[cfg(feature = "preserve-metadata")]
-            str: value.clone().into_boxed_str(),
-        }
-    }
-}
-
 // some info about string tables
 // https://developer.valvesoftware.com/wiki/Networking_Events_%26_Messages
 // https://developer.valvesoftware.com/wiki/Networking_Entities
 
-// TODO: merge string + hash into a single struct or something
-//
-// TODO: do not clone strings, but reference them instead -> introduce lifetimes
-// or build a symbol table from symbols (string cache?)
-
 /// note about missing `field_serializer_version` field (from
 /// [`valveprotos::common::ProtoFlattenedSerializerFieldT`]): i did not find any evidence of it
 /// being used nor any breakage or data corruptions. it is possible that i missed something. but
@@ -89,15 +59,16 @@ pub struct FlattenedSerializerField {
 // (protobuf mapping; metadata; field serializer construction).
 impl FlattenedSerializerField {
     fn new(
-        msg: &CsvcMsgFlattenedSerializer,
+        symbol_table: &SymbolTable,
+        symbols: &[Symbol],
         field: &ProtoFlattenedSerializerFieldT,
     ) -> Result<Self, FieldMetadataError> {
         // SAFETY: some symbols are cricual, if they don't exist - fail early
         // and loudly.
         //
         // TODO: do not call get_unchecked here! that's stupid.
-        let resolve_sym_unchecked = |i: i32| unsafe { msg.symbols.get_unchecked(i as usize) };
-        let resolve_sym = |v: i32| &msg.symbols[v as usize];
+        let resolve_sym_unchecked = |i: i32| unsafe { *symbols.get_unchecked(i as usize) };
+        let resolve_sym = |v: i32| symbols[v as usize];
 
         let var_type = unsafe {
             field
@@ -105,30 +76,26 @@ impl FlattenedSerializerField {
                 .map(resolve_sym_unchecked)
                 .unwrap_unchecked()
         };
-        let var_name = unsafe {
-            field
-                .var_name_sym
-                .map(resolve_sym_unchecked)
-                .unwrap_unchecked()
-        };
 
         let mut ret = Self {
-            var_type: Symbol::from(var_type),
-            var_name: Symbol::from(var_name),
+            var_type,
+            var_name: unsafe {
+                field
+                    .var_name_sym
+                    .map(resolve_sym_unchecked)
+                    .unwrap_unchecked()
+            },
             bit_count: field.bit_count,
             low_value: field.low_value,
             high_value: field.high_value,
             encode_flags: field.encode_flags,
-            field_serializer_name: field
-                .field_serializer_name_sym
-                .map(resolve_sym)
-                .map(Symbol::from),
-            var_encoder: field.var_encoder_sym.map(resolve_sym).map(Symbol::from),
+            field_serializer_name: field.field_serializer_name_sym.map(resolve_sym),
+            var_encoder: field.var_encoder_sym.map(resolve_sym),
 
             field_serializer: None,
             metadata: Default::default(),
         };
-        ret.metadata = get_field_metadata(&ret, var_type)?;
+        ret.metadata = get_field_metadata(&ret, symbol_table.resolve(var_type))?;
         Ok(ret)
     }
 
@@ -154,6 +121,14 @@ impl FlattenedSerializerField {
         self.var_encoder.as_ref().is_some_and(|lhs| lhs.hash == rhs)
     }
 
+    #[inline(always)]
+    pub fn is_fixed_array(&self) -> bool {
+        self.metadata
+            .special_descriptor
+            .as_ref()
+            .is_some_and(|sd| sd.is_fixed_array())
+    }
+
     #[inline(always)]
     pub fn is_dynamic_array(&self) -> bool {
         self.metadata
@@ -161,6 +136,14 @@ impl FlattenedSerializerField {
             .as_ref()
             .is_some_and(|sd| sd.is_dynamic_array())
     }
+
+    #[inline(always)]
+    pub fn is_pointer(&self) -> bool {
+        self.metadata
+            .special_descriptor
+            .as_ref()
+            .is_some_and(|sd| sd.is_pointer())
+    }
 }
 
 /// note about missing `serializer_version` field (from
@@ -181,10 +164,10 @@ pub struct FlattenedSerializer {
 }
 
 impl FlattenedSerializer {
-    fn new(msg: &CsvcMsgFlattenedSerializer, fs: &ProtoFlattenedSerializerT) -> Self {
+    fn new(symbols: &[Symbol], fs: &ProtoFlattenedSerializerT) -> Self {
         // SAFETY: some symbols are cricual, if they don't exist - fail early
         // and loudly.
-        let resolve_sym_unchecked = |i: i32| unsafe { msg.symbols.get_unchecked(i as usize) };
+        let resolve_sym_unchecked = |i: i32| unsafe { *symbols.get_unchecked(i as usize) };
 
         let serializer_name = unsafe {
             fs.serializer_name_sym
@@ -193,7 +176,7 @@ impl FlattenedSerializer {
         };
 
         Self {
-            serializer_name: Symbol::from(serializer_name),
+            serializer_name,
             fields: Vec::with_capacity(fs.fields_index.len()),
         }
     }
@@ -224,7 +207,11 @@ pub struct FlattenedSerializerContainer {
 }
 
 impl FlattenedSerializerContainer {
-    pub fn parse(cmd: CDemoSendTables) -> Result<Self, FlattenedSerializersError> {
+    /// strings (field names, types, etc.) are being interned into the given symbol table.
+    pub fn parse(
+        cmd: CDemoSendTables,
+        symbol_table: &mut SymbolTable,
+    ) -> Result<Self, FlattenedSerializersError> {
         let msg = {
             // TODO: make prost work with ByteString and turn data into Bytes
             //
@@ -243,6 +230,12 @@ impl FlattenedSerializerContainer {
             CsvcMsgFlattenedSerializer::decode(data)?
         };
 
+        let symbols: Vec<Symbol> = msg
+            .symbols
+            .iter()
+            .map(|symbol| symbol_table.intern(symbol))
+            .collect();
+
         let mut field_map: FieldMap =
             FieldMap::with_capacity_and_hasher(msg.fields.len(), BuildHasherDefault::default());
         let mut serializer_map: SerializerMap = SerializerMap::with_capacity_and_hasher(
@@ -251,7 +244,7 @@ impl FlattenedSerializerContainer {
         );
 
         for serializer in msg.serializers.iter() {
-            let mut flattened_serializer = FlattenedSerializer::new(&msg, serializer);
+            let mut flattened_serializer = FlattenedSerializer::new(&symbols, serializer);
 
             for field_index in serializer.fields_index.iter() {
                 if let Some(field) = field_map.get(field_index) {
@@ -259,12 +252,17 @@ impl FlattenedSerializerContainer {
                     continue;
                 }
 
-                let mut field =
-                    FlattenedSerializerField::new(&msg, &msg.fields[*field_index as usize])?;
+                let mut field = FlattenedSerializerField::new(
+                    symbol_table,
+                    &symbols,
+                    &msg.fields[*field_index as usize],
+                )?;
 
                 field.field_serializer = match field.metadata.special_descriptor {
                     Some(FieldSpecialDescriptor::FixedArray { length }) => {
                         let mut field = field.clone();
+                        // NOTE: elements are not arrays themselves.
+                        field.metadata.special_descriptor = None;
                         field.field_serializer = field
                             .field_serializer_name
                             .as_ref()
@@ -2,6 +2,7 @@ use std::cell::UnsafeCell;
 use std::num::ParseIntError;
 use std::rc::Rc;
 
+use crate::fxhash;
 use crate::stringtables::StringTable;
 
 pub(crate) const INSTANCE_BASELINE_TABLE_NAME: &str = "instancebaseline";
@@ -9,6 +10,11 @@ pub(crate) const INSTANCE_BASELINE_TABLE_NAME: &str = "instancebaseline";
 #[derive(Default)]
 pub(crate) struct InstanceBaseline {
     data: Vec<Option<Rc<UnsafeCell<Vec<u8>>>>>,
+    // NOTE: user data is shared with the string table and is being overwritten in place, thus
+    // there's nothing to compare against but hashes of bytes that were seen during previous update.
+    hashes: Vec<Option<u64>>,
+    // NOTE: class ids whose baselines changed since last call to drain_changed.
+    changed: Vec<i32>,
 }
 
 impl InstanceBaseline {
@@ -19,6 +25,7 @@ impl InstanceBaseline {
     ) -> Result<(), ParseIntError> {
         if self.data.len() < classes {
             self.data.resize(classes, None);
+            self.hashes.resize(classes, None);
         }
 
         for (_entity_index, item) in string_table.items() {
@@ -28,11 +35,25 @@ impl InstanceBaseline {
             let string =
                 unsafe { std::str::from_utf8_unchecked(item.string.as_ref().unwrap_unchecked()) };
             let class_id = string.parse::<i32>()?;
+            let hash = item
+                .user_data
+                .as_ref()
+                .map(|user_data| fxhash::hash_bytes(unsafe { &*user_data.get() }));
+            if self.hashes[class_id as usize] != hash {
+                self.hashes[class_id as usize] = hash;
+                self.changed.push(class_id);
+            }
             self.data[class_id as usize] = item.user_data.clone();
         }
         Ok(())
     }
 
+    /// returns class ids whose baselines changed since previous call.
+    #[inline]
+    pub(crate) fn drain_changed(&mut self) -> impl Iterator<Item = i32> + '_ {
+        self.changed.drain(..)
+    }
+
     #[inline]
     pub(crate) unsafe fn by_id_unchecked(&self, class_id: i32) -> &[u8] {
         unsafe {
@@ -48,5 +69,7 @@ impl InstanceBaseline {
     /// clear clears underlying storage, but this has no effect on the allocated capacity.
     pub(crate) fn clear(&mut self) {
         self.data.clear();
+        self.hashes.clear();
+        self.changed.clear();
     }
 }
@@ -4,20 +4,31 @@
 
 // TODO: figure pub scopes for all the things
 pub mod bitreader;
+pub mod bitwriter;
+pub mod democlip;
 pub mod demofile;
+#[cfg(feature = "bzip2")]
+pub mod demofilebz2;
 pub mod demostream;
+pub mod demowriter;
 pub mod entities;
 pub mod entityclasses;
 pub(crate) mod fielddecoder;
+pub mod fieldkey;
 pub(crate) mod fieldmetadata;
 pub mod fieldpath;
 pub mod fieldvalue;
 pub mod flattenedserializers;
 pub mod fxhash;
 pub(crate) mod instancebaseline;
+pub mod matchsummary;
 pub mod parser;
 pub(crate) mod quantizedfloat;
+pub mod spawngroups;
 pub mod stringtables;
+pub mod symboltable;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testdemo;
 
 // own crate re-exports
 pub(crate) use haste_vartype as vartype;
@@ -35,10 +46,6 @@ pub use valveprotos;
 // second as metric (inspired by
 // https://github.com/markus-wa/demoinfocs-golang?tab=readme-ov-file#performance--benchmarks).
 
-// TODO: don't ignore length of vectors (dynamic length arrays) because they may
-// contain garbage; see
-// https://github.com/markus-wa/demoinfocs-golang/issues/450 for details.
-
 // TODO: generate list of entities (/flattened serializers) where it'll be
 // possible to get "the thing" by name hash and construct it.
 // probably use RecvTable and RecvProp "terms".
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use valveprotos::common::c_game_info::c_dota_game_info::CPlayerInfo;
+use valveprotos::common::c_game_info::CDotaGameInfo;
+use valveprotos::common::CDemoFileInfo;
+
+#[derive(Debug, Clone)]
+pub struct PlayerSummary {
+    pub steam_id: u64,
+    pub player_name: String,
+    /// e.g. `npc_dota_hero_zuus` in dota 2.
+    pub hero_name: String,
+    /// 2 and 3 are radiant and dire in dota 2.
+    pub team: i32,
+    pub is_fake_client: bool,
+}
+
+impl From<&CPlayerInfo> for PlayerSummary {
+    fn from(player_info: &CPlayerInfo) -> Self {
+        Self {
+            steam_id: player_info.steamid(),
+            player_name: player_info.player_name().to_owned(),
+            hero_name: player_info.hero_name().to_owned(),
+            team: player_info.game_team(),
+            is_fake_client: player_info.is_fake_client(),
+        }
+    }
+}
+
+/// match summary that can be obtained without parsing the replay; see
+/// [`crate::demofile::DemoFile::match_summary`].
+///
+/// fields that come from game info are `None` (or empty) if replay's [`CDemoFileInfo`] does not
+/// have it (for example in replays of lobby matches).
+#[cfg_attr(
+    feature = "deadlock",
+    doc = "",
+    doc = "in deadlock replays game info is read from the dota sub-message of `CGameInfo` (there's \
+           no deadlock one); do not expect it to be filled in, playback fields are the only ones \
+           that are always there."
+)]
+#[derive(Debug, Clone)]
+pub struct MatchSummary {
+    /// duration of the replay (not of the game) in seconds.
+    pub playback_time: f32,
+    pub playback_ticks: i32,
+    pub playback_frames: i32,
+    pub match_id: Option<u64>,
+    pub game_mode: Option<i32>,
+    /// team (see [`PlayerSummary::team`]) that won.
+    pub game_winner: Option<i32>,
+    /// unix timestamp.
+    pub end_time: Option<u32>,
+    pub players: Vec<PlayerSummary>,
+}
+
+impl MatchSummary {
+    /// `None` if `playback_time` is negative, not finite or too large.
+    #[inline]
+    pub fn playback_duration(&self) -> Option<Duration> {
+        Duration::try_from_secs_f32(self.playback_time).ok()
+    }
+
+    /// same as [`MatchSummary::playback_duration`], but in milliseconds.
+    #[inline]
+    pub fn duration_ms(&self) -> Option<u64> {
+        self.playback_duration()
+            .and_then(|duration| u64::try_from(duration.as_millis()).ok())
+    }
+
+    pub fn players_in_team(&self, team: i32) -> impl Iterator<Item = &PlayerSummary> {
+        self.players
+            .iter()
+            .filter(move |player| player.team == team)
+    }
+}
+
+// NOTE: CGameInfo has dota and cs sub-messages only, there's no deadlock one; deadlock replays
+// can only carry game info in the dota one (see MatchSummary's docs).
+fn game_info(file_info: &CDemoFileInfo) -> Option<&CDotaGameInfo> {
+    file_info.game_info.as_ref()?.dota.as_ref()
+}
+
+impl From<&CDemoFileInfo> for MatchSummary {
+    fn from(file_info: &CDemoFileInfo) -> Self {
+        let game_info = game_info(file_info);
+
+        Self {
+            playback_time: file_info.playback_time(),
+            playback_ticks: file_info.playback_ticks(),
+            playback_frames: file_info.playback_frames(),
+            match_id: game_info.and_then(|gi| gi.match_id),
+            game_mode: game_info.and_then(|gi| gi.game_mode),
+            game_winner: game_info.and_then(|gi| gi.game_winner),
+            end_time: game_info.and_then(|gi| gi.end_time),
+            players: game_info
+                .map(|gi| gi.player_info.iter().map(PlayerSummary::from).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use valveprotos::common::CGameInfo;
+
+    use super::*;
+
+    fn match_summary(playback_time: f32) -> MatchSummary {
+        MatchSummary::from(&CDemoFileInfo {
+            playback_time: Some(playback_time),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_playback_duration() {
+        assert_eq!(
+            match_summary(90.5).playback_duration(),
+            Some(Duration::from_millis(90_500))
+        );
+        assert_eq!(match_summary(-1.0).playback_duration(), None);
+        assert_eq!(match_summary(f32::NAN).playback_duration(), None);
+    }
+
+    #[test]
+    fn test_game_info() {
+        let player_info = |steam_id: u64, hero_name: &str, team: i32| CPlayerInfo {
+            hero_name: Some(hero_name.to_string()),
+            player_name: Some(format!("player {steam_id}")),
+            is_fake_client: Some(false),
+            steamid: Some(steam_id),
+            game_team: Some(team),
+        };
+        let file_info = CDemoFileInfo {
+            playback_time: Some(2400.25),
+            playback_ticks: Some(72_007),
+            playback_frames: Some(36_003),
+            game_info: Some(CGameInfo {
+                dota: Some(CDotaGameInfo {
+                    match_id: Some(7_000_000_042),
+                    game_mode: Some(22),
+                    game_winner: Some(3),
+                    end_time: Some(1_700_000_000),
+                    player_info: vec![
+                        player_info(1, "npc_dota_hero_zuus", 2),
+                        player_info(2, "npc_dota_hero_axe", 3),
+                        player_info(3, "npc_dota_hero_lina", 3),
+                    ],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        };
+
+        let match_summary = MatchSummary::from(&file_info);
+        assert_eq!(match_summary.duration_ms(), Some(2_400_250));
+        assert_eq!(match_summary.playback_ticks, 72_007);
+        assert_eq!(match_summary.match_id, Some(7_000_000_042));
+        assert_eq!(match_summary.game_mode, Some(22));
+        assert_eq!(match_summary.end_time, Some(1_700_000_000));
+
+        let player = &match_summary.players[0];
+        assert_eq!(player.steam_id, 1);
+        assert_eq!(player.player_name, "player 1");
+        assert_eq!(player.hero_name, "npc_dota_hero_zuus");
+        assert_eq!(player.team, 2);
+        assert!(!player.is_fake_client);
+
+        assert_eq!(match_summary.game_winner, Some(3));
+        let winners: Vec<u64> = match_summary
+            .players_in_team(3)
+            .map(|player| player.steam_id)
+            .collect();
+        assert_eq!(winners, [2, 3]);
+        assert_eq!(match_summary.players_in_team(2).count(), 1);
+    }
+}
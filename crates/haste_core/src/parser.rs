@@ -9,13 +9,16 @@ use valveprotos::prost::Message;
 
 use crate::bitreader::BitReader;
 use crate::demofile::{DemoHeaderError, DEMO_RECORD_BUFFER_SIZE};
-use crate::demostream::{CmdHeader, DemoStream};
-use crate::entities::{DeltaHeader, Entity, EntityContainer};
+use crate::demostream::{CmdHeader, DemoStream, ReadCmdError, SeekableDemoStream};
+use crate::entities::{
+    DeltaHeader, Entity, EntityContainer, EntityCreateContext, EntityCreatedFrom,
+};
 use crate::entityclasses::EntityClasses;
 use crate::fielddecoder::FieldDecodeContext;
 use crate::flattenedserializers::FlattenedSerializerContainer;
 use crate::instancebaseline::{InstanceBaseline, INSTANCE_BASELINE_TABLE_NAME};
 use crate::stringtables::StringTableContainer;
+use crate::symboltable::SymbolTable;
 
 // as can be observed when dumping commands. also as specified in clarity
 // (src/main/java/skadistats/clarity/model/engine/AbstractDotaEngineType.java)
@@ -42,6 +45,7 @@ pub struct Context {
     serializers: Option<FlattenedSerializerContainer>,
     entity_classes: Option<EntityClasses>,
     entities: EntityContainer,
+    symbols: SymbolTable,
     tick_interval: f32,
     full_packet_interval: i32,
     tick: i32,
@@ -79,6 +83,12 @@ impl Context {
         }
     }
 
+    /// resolves [`crate::symboltable::Symbol`]s of serializers, their fields and class infos.
+    #[inline]
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
     #[inline]
     pub fn tick_interval(&self) -> f32 {
         self.tick_interval
@@ -90,6 +100,24 @@ impl Context {
     }
 }
 
+impl Context {
+    /// re-reads instancebaseline string table (if both it and entity classes are available) and
+    /// drops cached baseline entities of classes whose baselines changed.
+    fn update_instance_baseline(&mut self) -> Result<()> {
+        let (Some(string_table), Some(entity_classes)) = (
+            self.string_tables.find_table(INSTANCE_BASELINE_TABLE_NAME),
+            self.entity_classes.as_ref(),
+        ) else {
+            return Ok(());
+        };
+        self.instance_baseline
+            .update(string_table, entity_classes.classes)?;
+        self.entities
+            .invalidate_baselines(self.instance_baseline.drain_changed());
+        Ok(())
+    }
+}
+
 pub trait Visitor {
     // TODO: include updated fields (list of field paths?)
     #[allow(unused_variables)]
@@ -129,7 +157,8 @@ enum ControlFlow {
     /// handled in a different manner outside the regular flow.
     IgnoreCmd,
     /// stops further processing and indicates that any work performed during the current cycle
-    /// must be undone.
+    /// must be undone. cmd header is not unread (that's not possible with streams that can't
+    /// seek), run returns it instead.
     Break,
 }
 
@@ -141,6 +170,7 @@ pub struct Parser<D: DemoStream, V: Visitor> {
     ctx: Context,
     // NOTE(blukai): is this the place for this? can it be moved "closer" to entities somewhere?
     field_decode_ctx: FieldDecodeContext,
+    allow_truncated: bool,
 }
 
 impl<D: DemoStream, V: Visitor> Parser<D, V> {
@@ -155,12 +185,14 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                 instance_baseline: InstanceBaseline::default(),
                 serializers: None,
                 entity_classes: None,
+                symbols: SymbolTable::default(),
                 tick_interval: 0.0,
                 full_packet_interval: 0,
                 tick: -1,
                 prev_tick: -1,
             },
             field_decode_ctx: FieldDecodeContext::default(),
+            allow_truncated: false,
         })
     }
 
@@ -173,34 +205,40 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
     // recorded).
     //
     // must be publicly exposed for this to be actually useful.
-    fn run<F>(&mut self, mut handler: F) -> Result<()>
+    //
+    /// returns header of the cmd that caused [`ControlFlow::Break`].
+    fn run<F>(&mut self, mut handler: F) -> Result<Option<CmdHeader>>
     where
         F: FnMut(&mut Self, &CmdHeader) -> Result<ControlFlow>,
     {
+        // NOTE: ticks as they were before the last cmd; they are restored if the cmd turns out to
+        // be cut short, thus tick in the context remains the tick of the last complete cmd.
+        let mut ticks = (self.ctx.prev_tick, self.ctx.tick);
         loop {
             match self.demo_stream.read_cmd_header() {
                 Ok(cmd_header) => {
-                    self.ctx.prev_tick = self.ctx.tick;
-                    self.ctx.tick = cmd_header.tick;
-                    match handler(self, &cmd_header)? {
-                        ControlFlow::HandleCmd => {
-                            self.handle_cmd(&cmd_header)?;
-                            if self.ctx.prev_tick != self.ctx.tick {
-                                self.visitor.on_tick_end(&self.ctx)?;
-                            }
-                        }
-                        ControlFlow::SkipCmd => self.demo_stream.skip_cmd(&cmd_header)?,
-                        ControlFlow::IgnoreCmd => {}
-                        ControlFlow::Break => {
-                            self.demo_stream.unread_cmd_header(&cmd_header)?;
-                            self.ctx.tick = self.ctx.prev_tick;
-                            return Ok(());
+                    ticks = (self.ctx.prev_tick, self.ctx.tick);
+                    match self.run_cmd(&mut handler, &cmd_header) {
+                        Ok(ControlFlow::Break) => return Ok(Some(cmd_header)),
+                        Ok(_) => {}
+                        Err(err) if self.allow_truncated && is_unexpected_eof(&err) => {
+                            (self.ctx.prev_tick, self.ctx.tick) = ticks;
+                            return Ok(None);
                         }
+                        Err(err) => return Err(err),
                     }
                 }
                 Err(err) => {
                     if self.demo_stream.is_at_eof().unwrap_or_default() {
-                        return Ok(());
+                        return Ok(None);
+                    }
+                    // NOTE: cmd header may be cut short; or previous cmd could have been skipped
+                    // past the end of the stream (seeking past the end is not an error).
+                    if self.allow_truncated
+                        && self.demo_stream.is_at_or_past_eof().unwrap_or_default()
+                    {
+                        (self.ctx.prev_tick, self.ctx.tick) = ticks;
+                        return Ok(None);
                     }
                     return Err(err.into());
                 }
@@ -208,89 +246,32 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
         }
     }
 
-    pub fn run_to_end(&mut self) -> Result<()> {
-        self.run(|_notnotself, _cmd_header| Ok(ControlFlow::HandleCmd))
-    }
-
-    fn reset(&mut self) -> Result<(), io::Error> {
-        self.demo_stream
-            .seek(SeekFrom::Start(self.demo_stream.start_position()))?;
-
-        self.ctx.entities.clear();
-        self.ctx.string_tables.clear();
-        self.ctx.instance_baseline.clear();
-        self.ctx.tick = -1;
-        self.ctx.prev_tick = -1;
-
-        Ok(())
-    }
-
-    pub fn run_to_tick(&mut self, target_tick: i32) -> Result<()> {
-        // TODO: do not allow tick to be less then -1
-
-        // TODO: do not allow tick to be greater then total ticks
-
-        // TODO: do not clear if seeking forward and there's no full packet on
-        // the way to the wanted tick / if target tick is closer then full
-        // packet interval
-        self.reset()?;
-
-        // NOTE: EDemoCommands::DemSyncTick is the last command with 4294967295
-        // tick (normlized to -1). last "initialization" command.
-        let mut did_handle_first_sync_tick = false;
-
-        // NOTE: EDemoCommands::DemFullPacket contains snapshot of everything...
-        // everything? it does not seem like it: string tables must be handled.
-        let mut did_handle_last_full_packet = false;
-
-        self.run(|notnotself, cmd_header| {
-            if cmd_header.tick > target_tick {
-                return Ok(ControlFlow::Break);
-            }
-
-            // init string tables, flattened serializers and entity classes
-            if !did_handle_first_sync_tick {
-                did_handle_first_sync_tick = cmd_header.cmd == EDemoCommands::DemSyncTick;
-                return Ok(ControlFlow::HandleCmd);
-            }
-
-            let is_full_packet = cmd_header.cmd == EDemoCommands::DemFullPacket;
-            let distance_to_target_tick = target_tick - notnotself.ctx.tick;
-            // TODO: what if there's no full packet ahead? maybe dem file is
-            // corrupted or something... scan for full packets before enterint
-            // the "run"?
-            let has_full_packet_ahead =
-                distance_to_target_tick > notnotself.ctx.full_packet_interval + 100;
-            if is_full_packet {
-                let cmd_body = notnotself.demo_stream.read_cmd(cmd_header)?;
-                notnotself
-                    .visitor
-                    .on_cmd(&notnotself.ctx, cmd_header, cmd_body)?;
-
-                let mut cmd = D::decode_cmd_full_packet(cmd_body)?;
-                if has_full_packet_ahead {
-                    // NOTE: clarity seem to ignore "intermediary" full packet's
-                    // packet
-                    //
-                    // TODO: verify that is okay to ignore "intermediary" full
-                    // packet's packet
-                    cmd.packet = None;
+    fn run_cmd<F>(&mut self, handler: &mut F, cmd_header: &CmdHeader) -> Result<ControlFlow>
+    where
+        F: FnMut(&mut Self, &CmdHeader) -> Result<ControlFlow>,
+    {
+        self.ctx.prev_tick = self.ctx.tick;
+        self.ctx.tick = cmd_header.tick;
+        let control_flow = handler(self, cmd_header)?;
+        match control_flow {
+            ControlFlow::HandleCmd => {
+                self.handle_cmd(cmd_header)?;
+                if self.ctx.prev_tick != self.ctx.tick {
+                    self.visitor.on_tick_end(&self.ctx)?;
                 }
-                notnotself.handle_cmd_full_packet(cmd)?;
-                // NOTE: there's absolutely no reason to check if tick changed because it changed.
-                notnotself.visitor.on_tick_end(&notnotself.ctx)?;
-
-                did_handle_last_full_packet = !has_full_packet_ahead;
-
-                return Ok(ControlFlow::IgnoreCmd);
             }
-
-            if did_handle_last_full_packet {
-                Ok(ControlFlow::HandleCmd)
-            } else {
-                Ok(ControlFlow::SkipCmd)
+            ControlFlow::SkipCmd => self.demo_stream.skip_cmd(cmd_header)?,
+            ControlFlow::IgnoreCmd => {}
+            ControlFlow::Break => {
+                self.ctx.tick = self.ctx.prev_tick;
             }
-        })
+        }
+        Ok(control_flow)
+    }
+
+    pub fn run_to_end(&mut self) -> Result<()> {
+        self.run(|_notnotself, _cmd_header| Ok(ControlFlow::HandleCmd))
+            .map(|_| ())
     }
 
     // important initialization messages:
@@ -318,7 +299,10 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                 }
 
                 let cmd = D::decode_cmd_send_tables(cmd_body)?;
-                self.ctx.serializers = Some(FlattenedSerializerContainer::parse(cmd)?);
+                self.ctx.serializers = Some(FlattenedSerializerContainer::parse(
+                    cmd,
+                    &mut self.ctx.symbols,
+                )?);
             }
 
             EDemoCommands::DemClassInfo => {
@@ -329,25 +313,14 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                 }
 
                 let cmd = D::decode_cmd_class_info(cmd_body)?;
-                self.ctx.entity_classes = Some(EntityClasses::parse(cmd));
+                self.ctx.entity_classes = Some(EntityClasses::parse(cmd, &mut self.ctx.symbols));
 
                 // NOTE: DemClassInfo message becomes available after
                 // SvcCreateStringTable(which has instancebaselines). to know
                 // how long vec that will contain instancebaseline values needs
                 // to be (to allocate precicely how much we need) we need to
                 // wait for DemClassInfos.
-                if let Some(string_table) = self
-                    .ctx
-                    .string_tables
-                    .find_table(INSTANCE_BASELINE_TABLE_NAME)
-                {
-                    // SAFETY: entity_classes value was assigned above ^.
-                    let entity_classes =
-                        unsafe { self.ctx.entity_classes.as_ref().unwrap_unchecked() };
-                    self.ctx
-                        .instance_baseline
-                        .update(string_table, entity_classes.classes)?;
-                }
+                self.ctx.update_instance_baseline()?;
             }
 
             _ => {
@@ -413,66 +386,18 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
     }
 
     fn handle_svc_create_string_table(&mut self, msg: CsvcMsgCreateStringTable) -> Result<()> {
-        let string_table = self.ctx.string_tables.create_string_table_mut(
-            msg.name(),
-            msg.user_data_fixed_size(),
-            msg.user_data_size(),
-            msg.user_data_size_bits(),
-            msg.flags(),
-            msg.using_varint_bitcounts(),
-        );
-
-        let string_data = if msg.data_compressed() {
-            let sd = msg.string_data();
-            let decompress_len = snap::raw::decompress_len(sd)?;
-            snap::raw::Decoder::new().decompress(sd, &mut self.buf)?;
-            &self.buf[..decompress_len]
-        } else {
-            msg.string_data()
-        };
-
-        let mut br = BitReader::new(string_data);
-        string_table.parse_update(&mut br, msg.num_entries())?;
-        br.is_overflowed()?;
-
+        let string_table = self.ctx.string_tables.handle_create_msg(&msg)?;
         if string_table.name().eq(INSTANCE_BASELINE_TABLE_NAME) {
-            if let Some(entity_classes) = self.ctx.entity_classes.as_ref() {
-                self.ctx
-                    .instance_baseline
-                    .update(string_table, entity_classes.classes)?;
-            }
+            self.ctx.update_instance_baseline()?;
         }
-
         Ok(())
     }
 
     fn handle_svc_update_string_table(&mut self, msg: CsvcMsgUpdateStringTable) -> Result<()> {
-        debug_assert!(msg.table_id.is_some(), "invalid table id");
-        let table_id = msg.table_id() as usize;
-
-        debug_assert!(
-            self.ctx.string_tables.has_table(table_id),
-            "tryting to update non-existent table"
-        );
-        let string_table = unsafe {
-            self.ctx
-                .string_tables
-                .get_table_mut(table_id)
-                .unwrap_unchecked()
-        };
-
-        let mut br = BitReader::new(msg.string_data());
-        string_table.parse_update(&mut br, msg.num_changed_entries())?;
-        br.is_overflowed()?;
-
+        let string_table = self.ctx.string_tables.handle_update_msg(&msg)?;
         if string_table.name().eq(INSTANCE_BASELINE_TABLE_NAME) {
-            if let Some(entity_classes) = self.ctx.entity_classes.as_ref() {
-                self.ctx
-                    .instance_baseline
-                    .update(string_table, entity_classes.classes)?;
-            }
+            self.ctx.update_instance_baseline()?;
         }
-
         Ok(())
     }
 
@@ -484,7 +409,21 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
         // entities.
         let entity_classes = unsafe { self.ctx.entity_classes.as_ref().unwrap_unchecked() };
         let serializers = unsafe { self.ctx.serializers.as_ref().unwrap_unchecked() };
-        let instance_baseline = &self.ctx.instance_baseline;
+
+        let tick = self.ctx.tick;
+        let create_ctx = EntityCreateContext {
+            tick,
+            // NOTE: entities that are being created by non-delta (full) updates might have existed
+            // before.
+            created_from: if msg.is_delta() {
+                EntityCreatedFrom::Delta
+            } else {
+                EntityCreatedFrom::FullPacket
+            },
+            entity_classes,
+            instance_baseline: &self.ctx.instance_baseline,
+            serializers,
+        };
 
         let entity_data = msg.entity_data();
         let mut br = BitReader::new(entity_data);
@@ -502,11 +441,9 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                     let entity = unsafe {
                         let entity = self.ctx.entities.handle_create(
                             entity_index,
+                            &create_ctx,
                             &mut self.field_decode_ctx,
                             &mut br,
-                            entity_classes,
-                            instance_baseline,
-                            serializers,
                         )?;
                         // SAFETY: borrow checker is not happy because handle_create requires
                         // mutable access to entities; rust's borrowing rules specify that you
@@ -529,6 +466,7 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                     let entity = unsafe {
                         let entity = self.ctx.entities.handle_update_unchecked(
                             entity_index,
+                            tick,
                             &mut self.field_decode_ctx,
                             &mut br,
                         )?;
@@ -547,20 +485,7 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
 
     fn handle_cmd_string_tables(&mut self, cmd: CDemoStringTables) -> Result<()> {
         self.ctx.string_tables.do_full_update(cmd);
-
-        // SAFETY: entity_classes value is expected to be already assigned
-        let entity_classes = unsafe { self.ctx.entity_classes.as_ref().unwrap_unchecked() };
-        if let Some(string_table) = self
-            .ctx
-            .string_tables
-            .find_table(INSTANCE_BASELINE_TABLE_NAME)
-        {
-            self.ctx
-                .instance_baseline
-                .update(string_table, entity_classes.classes)?;
-        }
-
-        Ok(())
+        self.ctx.update_instance_baseline()
     }
 
     fn handle_cmd_full_packet(&mut self, cmd: CDemoFullPacket) -> Result<()> {
@@ -592,6 +517,132 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
     pub fn context(&self) -> &Context {
         &self.ctx
     }
+
+    /// when enabled, unexpected end of stream in the middle of a cmd is treated as the end of the
+    /// replay instead of as an error. this allows to parse replays that were cut short (crashed
+    /// server, partial download). the cmd that was cut short gets dropped, tick (see
+    /// [`Context::tick`]) remains the tick of the last complete cmd.
+    ///
+    /// disabled by default.
+    #[inline]
+    pub fn set_allow_truncated(&mut self, allow_truncated: bool) {
+        self.allow_truncated = allow_truncated;
+    }
+}
+
+// NOTE: those need seeking.
+impl<D: SeekableDemoStream, V: Visitor> Parser<D, V> {
+    fn reset(&mut self) -> Result<(), io::Error> {
+        self.demo_stream
+            .seek(SeekFrom::Start(self.demo_stream.start_position()))?;
+
+        self.ctx.entities.clear();
+        self.ctx.string_tables.clear();
+        self.ctx.instance_baseline.clear();
+        self.ctx.tick = -1;
+        self.ctx.prev_tick = -1;
+
+        Ok(())
+    }
+
+    pub fn run_to_tick(&mut self, target_tick: i32) -> Result<()> {
+        // TODO: do not allow tick to be less then -1
+
+        // NOTE: target tick is not checked against total_ticks up front; for streams that grow
+        // (for example BroadcastHttp) total_ticks is not known without a scan. if the stream ends
+        // before the target tick that'll be an error below.
+
+        // TODO: do not clear if seeking forward and there's no full packet on
+        // the way to the wanted tick / if target tick is closer then full
+        // packet interval
+        self.reset()?;
+
+        // NOTE: EDemoCommands::DemSyncTick is the last command with 4294967295
+        // tick (normlized to -1). last "initialization" command.
+        let mut did_handle_first_sync_tick = false;
+
+        // NOTE: EDemoCommands::DemFullPacket contains snapshot of everything...
+        // everything? it does not seem like it: string tables must be handled.
+        let mut did_handle_last_full_packet = false;
+
+        let break_cmd_header = self.run(|notnotself, cmd_header| {
+            if cmd_header.tick > target_tick {
+                return Ok(ControlFlow::Break);
+            }
+
+            // init string tables, flattened serializers and entity classes
+            if !did_handle_first_sync_tick {
+                did_handle_first_sync_tick = cmd_header.cmd == EDemoCommands::DemSyncTick;
+                return Ok(ControlFlow::HandleCmd);
+            }
+
+            let is_full_packet = cmd_header.cmd == EDemoCommands::DemFullPacket;
+            let distance_to_target_tick = target_tick - notnotself.ctx.tick;
+            // TODO: what if there's no full packet ahead? maybe dem file is
+            // corrupted or something... scan for full packets before enterint
+            // the "run"?
+            let has_full_packet_ahead =
+                distance_to_target_tick > notnotself.ctx.full_packet_interval + 100;
+            if is_full_packet {
+                let cmd_body = notnotself.demo_stream.read_cmd(cmd_header)?;
+                notnotself
+                    .visitor
+                    .on_cmd(&notnotself.ctx, cmd_header, cmd_body)?;
+
+                let mut cmd = D::decode_cmd_full_packet(cmd_body)?;
+                if has_full_packet_ahead {
+                    // NOTE: clarity seem to ignore "intermediary" full packet's
+                    // packet
+                    //
+                    // TODO: verify that is okay to ignore "intermediary" full
+                    // packet's packet
+                    cmd.packet = None;
+                }
+                notnotself.handle_cmd_full_packet(cmd)?;
+                // NOTE: there's absolutely no reason to check if tick changed because it changed.
+                notnotself.visitor.on_tick_end(&notnotself.ctx)?;
+
+                did_handle_last_full_packet = !has_full_packet_ahead;
+
+                return Ok(ControlFlow::IgnoreCmd);
+            }
+
+            if did_handle_last_full_packet {
+                Ok(ControlFlow::HandleCmd)
+            } else {
+                Ok(ControlFlow::SkipCmd)
+            }
+        })?;
+
+        if let Some(cmd_header) = break_cmd_header {
+            self.demo_stream.unread_cmd_header(&cmd_header)?;
+
+            // NOTE: streams that do not retain everything (for example bounded broadcast buffers)
+            // can begin past the target tick; there's nothing to seek to.
+            if target_tick >= 0 && self.ctx.tick < 0 {
+                anyhow::bail!(
+                    "target tick {target_tick} precedes the first tick of the stream ({})",
+                    cmd_header.tick
+                );
+            }
+        } else if target_tick > self.ctx.tick {
+            anyhow::bail!(
+                "target tick {target_tick} is past the last tick ({})",
+                self.ctx.tick
+            );
+        }
+        Ok(())
+    }
+}
+
+fn is_unexpected_eof(err: &anyhow::Error) -> bool {
+    err.chain().any(|err| {
+        if let Some(ReadCmdError::IoError(err)) = err.downcast_ref::<ReadCmdError>() {
+            return err.kind() == io::ErrorKind::UnexpectedEof;
+        }
+        err.downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::UnexpectedEof)
+    })
 }
 
 pub struct NopVisitor;
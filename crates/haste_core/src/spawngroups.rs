@@ -0,0 +1,113 @@
+use valveprotos::common::{CDemoSpawnGroups, CnetMsgSpawnGroupLoad};
+use valveprotos::prost::{self, Message};
+
+// NOTE: each msg within CDemoSpawnGroups is an encoded CNETMsg_SpawnGroup_Load; the same msg that
+// the server sends when spawn group gets loaded (NetMessages::NetSpawnGroupLoad).
+
+#[derive(Debug)]
+pub struct SpawnGroups {
+    spawn_groups: Vec<CnetMsgSpawnGroupLoad>,
+}
+
+impl SpawnGroups {
+    pub fn parse(cmd: CDemoSpawnGroups) -> Result<Self, prost::DecodeError> {
+        let spawn_groups = cmd
+            .msgs
+            .iter()
+            .map(|msg| CnetMsgSpawnGroupLoad::decode(msg.as_slice()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { spawn_groups })
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &CnetMsgSpawnGroupLoad> {
+        self.spawn_groups.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.spawn_groups.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.spawn_groups.is_empty()
+    }
+
+    pub fn by_handle(&self, spawn_group_handle: u32) -> Option<&CnetMsgSpawnGroupLoad> {
+        self.spawn_groups
+            .iter()
+            .find(|spawn_group| spawn_group.spawngrouphandle() == spawn_group_handle)
+    }
+
+    /// returns world name of the first spawn group that has one (that is the map; for example
+    /// `maps/dota.vpk`). spawn groups without world name (for example ones that only carry
+    /// entities) are skipped; subsequent ones are usually map variants / sub-worlds that are
+    /// loaded on top of it.
+    pub fn world_name(&self) -> Option<&str> {
+        self.spawn_groups
+            .iter()
+            .map(|spawn_group| spawn_group.worldname())
+            .find(|world_name| !world_name.is_empty())
+    }
+
+    /// returns raw (not parsed) resource manifests of all spawn groups.
+    pub fn manifests(&self) -> impl Iterator<Item = &[u8]> {
+        self.spawn_groups
+            .iter()
+            .map(|spawn_group| spawn_group.spawngroupmanifest())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spawn_group(handle: u32, world_name: Option<&str>, manifest: &[u8]) -> Vec<u8> {
+        CnetMsgSpawnGroupLoad {
+            worldname: world_name.map(ToString::to_string),
+            spawngrouphandle: Some(handle),
+            spawngroupmanifest: Some(manifest.to_vec()),
+            ..Default::default()
+        }
+        .encode_to_vec()
+    }
+
+    #[test]
+    fn test_parse() -> Result<(), prost::DecodeError> {
+        let cmd = CDemoSpawnGroups {
+            msgs: vec![
+                spawn_group(1, None, b"entities"),
+                spawn_group(2, Some("maps/dota.vpk"), b"map"),
+                spawn_group(3, Some("maps/dota_variant.vpk"), b"variant"),
+            ],
+        };
+        let spawn_groups = SpawnGroups::parse(cmd)?;
+
+        assert_eq!(spawn_groups.len(), 3);
+        assert!(!spawn_groups.is_empty());
+        let handles: Vec<u32> = spawn_groups
+            .iter()
+            .map(|spawn_group| spawn_group.spawngrouphandle())
+            .collect();
+        assert_eq!(handles, [1, 2, 3]);
+
+        assert_eq!(
+            spawn_groups
+                .by_handle(3)
+                .map(|spawn_group| spawn_group.worldname()),
+            Some("maps/dota_variant.vpk")
+        );
+        assert!(spawn_groups.by_handle(4).is_none());
+
+        assert_eq!(spawn_groups.world_name(), Some("maps/dota.vpk"));
+        let manifests: Vec<&[u8]> = spawn_groups.manifests().collect();
+        assert_eq!(manifests, [b"entities".as_slice(), b"map", b"variant"]);
+
+        let spawn_groups = SpawnGroups::parse(CDemoSpawnGroups::default())?;
+        assert!(spawn_groups.is_empty());
+        assert_eq!(spawn_groups.world_name(), None);
+
+        Ok(())
+    }
+}
@@ -5,9 +5,22 @@ use std::rc::Rc;
 
 use hashbrown::HashMap;
 use nohash::NoHashHasher;
-use valveprotos::common::{c_demo_string_tables, CDemoStringTables};
-
-use crate::bitreader::BitReader;
+use valveprotos::common::{
+    c_demo_string_tables, CDemoStringTables, CsvcMsgCreateStringTable, CsvcMsgUpdateStringTable,
+};
+
+use crate::bitreader::{BitReader, BitReaderOverflowError};
+use crate::bitwriter::BitWriter;
+
+#[derive(thiserror::Error, Debug)]
+pub enum StringTableError {
+    #[error(transparent)]
+    SnapError(#[from] snap::Error),
+    #[error(transparent)]
+    BitReaderOverflowError(#[from] BitReaderOverflowError),
+    #[error("string table {0} does not exist")]
+    TableNotFound(i32),
+}
 
 // NOTE: some info about string tables is available at
 // https://developer.valvesoftware.com/wiki/Networking_Events_%26_Messages#String_Tables
@@ -296,6 +309,113 @@ impl StringTable {
     pub fn get_item(&self, entry_index: &i32) -> Option<&StringTableItem> {
         self.items.get(entry_index)
     }
+
+    /// the inverse of [`StringTable::do_full_update`]. items are positional there, thus gaps (if
+    /// there are any) are filled with empty items.
+    pub fn to_snapshot(&self) -> c_demo_string_tables::TableT {
+        let len = self.items.keys().max().map_or(0, |max| max + 1);
+        let items = (0..len)
+            .map(|entry_index| {
+                let item = self.items.get(&entry_index);
+                c_demo_string_tables::ItemsT {
+                    str: item
+                        .and_then(|item| item.string.as_ref())
+                        .map(|string| String::from_utf8_lossy(string).into_owned()),
+                    data: item
+                        .and_then(|item| item.user_data.as_ref())
+                        .map(|user_data| unsafe { &*user_data.get() }.clone()),
+                }
+            })
+            .collect();
+        c_demo_string_tables::TableT {
+            table_name: Some(self.name.to_string()),
+            items,
+            table_flags: Some(self.flags),
+            ..Default::default()
+        }
+    }
+
+    /// the inverse of [`StringTable::parse_update`]. all entries are being encoded; string
+    /// history is not used and user data is not compressed.
+    fn encode_entries(&self) -> (i32, Vec<u8>) {
+        let mut entry_indices: Vec<i32> = self.items.keys().copied().collect();
+        entry_indices.sort_unstable();
+
+        let mut bw = BitWriter::new();
+        let mut prev_entry_index = -1;
+        for entry_index in entry_indices.iter().copied() {
+            let item = &self.items[&entry_index];
+
+            bw.write_bool(entry_index == prev_entry_index + 1);
+            if entry_index != prev_entry_index + 1 {
+                bw.write_uvarint32((entry_index - 1) as u32);
+            }
+            prev_entry_index = entry_index;
+
+            bw.write_bool(item.string.is_some());
+            if let Some(string) = item.string.as_ref() {
+                bw.write_bool(false);
+                bw.write_bytes(string);
+                bw.write_byte(0);
+            }
+
+            let user_data = item
+                .user_data
+                .as_ref()
+                .map(|user_data| unsafe { &*user_data.get() });
+            bw.write_bool(user_data.is_some());
+            if let Some(user_data) = user_data {
+                if self.user_data_fixed_size {
+                    let num_bits = self.user_data_size_bits as usize;
+                    let (num_bytes, num_bits_left) = (num_bits / 8, num_bits % 8);
+                    bw.write_bytes(&user_data[..num_bytes]);
+                    if num_bits_left > 0 {
+                        bw.write_ubit64(user_data[num_bytes] as u64, num_bits_left);
+                    }
+                } else {
+                    if (self.flags & 0x1) != 0 {
+                        bw.write_bool(false);
+                    }
+                    if self.using_varint_bitcounts {
+                        bw.write_ubitvar(user_data.len() as u32);
+                    } else {
+                        bw.write_ubit64(user_data.len() as u64, MAX_USERDATA_BITS);
+                    }
+                    bw.write_bytes(user_data);
+                }
+            }
+        }
+
+        (entry_indices.len() as i32, bw.into_inner())
+    }
+
+    /// encodes all entries into [`CsvcMsgCreateStringTable`] that re-creates the table.
+    pub fn to_create_msg(&self) -> CsvcMsgCreateStringTable {
+        let (num_entries, string_data) = self.encode_entries();
+        CsvcMsgCreateStringTable {
+            name: Some(self.name.to_string()),
+            num_entries: Some(num_entries),
+            user_data_fixed_size: Some(self.user_data_fixed_size),
+            user_data_size: Some(self.user_data_size),
+            user_data_size_bits: Some(self.user_data_size_bits),
+            flags: Some(self.flags),
+            string_data: Some(string_data),
+            data_compressed: Some(false),
+            using_varint_bitcounts: Some(self.using_varint_bitcounts),
+            ..Default::default()
+        }
+    }
+
+    /// encodes all entries into [`CsvcMsgUpdateStringTable`]; applying it to a table with the
+    /// same id brings entries up to date with this table.
+    pub fn to_update_msg(&self, table_id: i32) -> CsvcMsgUpdateStringTable {
+        let (num_changed_entries, string_data) = self.encode_entries();
+        CsvcMsgUpdateStringTable {
+            table_id: Some(table_id),
+            num_changed_entries: Some(num_changed_entries),
+            string_data: Some(string_data),
+        }
+    }
 }
 
 // NOTE: this is modelled after CNetworkStringTableContainer
@@ -334,6 +454,54 @@ impl StringTableContainer {
         &mut self.tables[len]
     }
 
+    /// creates a table out of [`CsvcMsgCreateStringTable`] and fills it in with entries of the
+    /// message.
+    pub fn handle_create_msg(
+        &mut self,
+        msg: &CsvcMsgCreateStringTable,
+    ) -> Result<&mut StringTable, StringTableError> {
+        let string_table = self.create_string_table_mut(
+            msg.name(),
+            msg.user_data_fixed_size(),
+            msg.user_data_size(),
+            msg.user_data_size_bits(),
+            msg.flags(),
+            msg.using_varint_bitcounts(),
+        );
+
+        let decompressed;
+        let string_data = if msg.data_compressed() {
+            decompressed = snap::raw::Decoder::new().decompress_vec(msg.string_data())?;
+            decompressed.as_slice()
+        } else {
+            msg.string_data()
+        };
+
+        let mut br = BitReader::new(string_data);
+        string_table.parse_update(&mut br, msg.num_entries())?;
+        br.is_overflowed()?;
+
+        Ok(string_table)
+    }
+
+    /// applies [`CsvcMsgUpdateStringTable`] to the table that it refers to.
+    pub fn handle_update_msg(
+        &mut self,
+        msg: &CsvcMsgUpdateStringTable,
+    ) -> Result<&mut StringTable, StringTableError> {
+        let table_id = msg.table_id();
+        let string_table = usize::try_from(table_id)
+            .ok()
+            .and_then(|table_id| self.tables.get_mut(table_id))
+            .ok_or(StringTableError::TableNotFound(table_id))?;
+
+        let mut br = BitReader::new(msg.string_data());
+        string_table.parse_update(&mut br, msg.num_changed_entries())?;
+        br.is_overflowed()?;
+
+        Ok(string_table)
+    }
+
     pub fn do_full_update(&mut self, cmd: CDemoStringTables) {
         for incoming in &cmd.tables {
             if let Some(existing) = self.find_table_mut(incoming.table_name()) {
@@ -404,4 +572,94 @@ impl StringTableContainer {
     pub fn tables(&self) -> impl Iterator<Item = &StringTable> {
         self.tables.iter()
     }
+
+    /// the inverse of [`StringTableContainer::do_full_update`]; this is what
+    /// [`valveprotos::common::CDemoFullPacket`] carries.
+    pub fn to_snapshot(&self) -> CDemoStringTables {
+        CDemoStringTables {
+            tables: self.tables.iter().map(StringTable::to_snapshot).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type Items = Vec<(i32, Option<Vec<u8>>, Option<Vec<u8>>)>;
+
+    fn items(string_table: &StringTable) -> Items {
+        let mut items: Items = string_table
+            .items()
+            .map(|(entry_index, item)| {
+                let user_data = item
+                    .user_data
+                    .as_ref()
+                    .map(|user_data| unsafe { &*user_data.get() }.clone());
+                (*entry_index, item.string.clone(), user_data)
+            })
+            .collect();
+        items.sort();
+        items
+    }
+
+    fn insert_item(string_table: &mut StringTable, entry_index: i32, string: &str, data: &[u8]) {
+        string_table.items.insert(
+            entry_index,
+            StringTableItem {
+                string: Some(string.as_bytes().to_vec()),
+                user_data: Some(Rc::new(UnsafeCell::new(data.to_vec()))),
+            },
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trip() -> Result<(), StringTableError> {
+        let mut string_tables = StringTableContainer::default();
+
+        let varint = string_tables.create_string_table_mut("varint", false, 0, 0, 0, true);
+        insert_item(varint, 0, "a", &[1, 2, 3]);
+        insert_item(varint, 1, "b", &[]);
+        // NOTE: gaps are encoded as index jumps
+        insert_item(varint, 5, "f", &[4]);
+        varint.items.insert(
+            6,
+            StringTableItem {
+                string: None,
+                user_data: None,
+            },
+        );
+
+        // flag 0x1 means that user data might be compressed
+        let compressible =
+            string_tables.create_string_table_mut("compressible", false, 0, 0, 1, false);
+        insert_item(compressible, 0, "a", &[7; 300]);
+
+        let fixed = string_tables.create_string_table_mut("fixed", true, 2, 12, 0, false);
+        insert_item(fixed, 0, "a", &[0xab, 0x0c]);
+        insert_item(fixed, 2, "c", &[0xcd, 0x0e]);
+
+        // create messages re-create tables
+        let mut created = StringTableContainer::default();
+        for string_table in string_tables.tables() {
+            let string_table_copy = created.handle_create_msg(&string_table.to_create_msg())?;
+            assert_eq!(items(string_table_copy), items(string_table));
+        }
+
+        // update messages bring existing tables up to date
+        let mut updated = StringTableContainer::default();
+        for (table_id, string_table) in string_tables.tables().enumerate() {
+            let create_msg = CsvcMsgCreateStringTable {
+                num_entries: Some(0),
+                string_data: None,
+                ..string_table.to_create_msg()
+            };
+            updated.handle_create_msg(&create_msg)?;
+            let update_msg = string_table.to_update_msg(table_id as i32);
+            let string_table_copy = updated.handle_update_msg(&update_msg)?;
+            assert_eq!(items(string_table_copy), items(string_table));
+        }
+
+        Ok(())
+    }
 }
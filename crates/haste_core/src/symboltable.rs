@@ -0,0 +1,165 @@
+use std::hash::BuildHasherDefault;
+
+use hashbrown::HashMap;
+use hashbrown::hash_map::Entry;
+use nohash::NoHashHasher;
+
+use crate::fxhash;
+
+// valve's implementation: public/tier1/utlsymbol.h
+//
+// NOTE: servo's string cache is not used because it's super slow; it relies on rust-phf that uses
+// sip13 cryptograpgic hasher, and you can't replace it with something else (without forking it
+// really).
+
+/// interned string. it is cheap to copy and compare. use [`SymbolTable::resolve`] to get the
+/// string back.
+///
+/// hash is exposed because lookups (serializers, entity fields, etc.) are hash-based.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    pub hash: u64,
+    id: u32,
+}
+
+impl Symbol {
+    #[inline(always)]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// parser-wide string interner; each distinct string is being held once. available via
+/// [`crate::parser::Context::symbols`].
+#[derive(Debug)]
+pub struct SymbolTable {
+    strs: Vec<Box<str>>,
+    ids: HashMap<u64, u32, BuildHasherDefault<NoHashHasher<u64>>>,
+    // NOTE: strings whose hash collided with an already interned one (hash, id). collisions are
+    // not expected to be common, thus linear scan is good enough.
+    collisions: Vec<(u64, u32)>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        let mut this = Self {
+            strs: Vec::default(),
+            ids: HashMap::default(),
+            collisions: Vec::default(),
+        };
+        // NOTE: default symbol (id 0; hash 0) resolves into an empty string.
+        this.intern("");
+        this
+    }
+}
+
+impl SymbolTable {
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        self.intern_with_hash(fxhash::hash_bytes(s.as_bytes()), s)
+    }
+
+    fn intern_with_hash(&mut self, hash: u64, s: &str) -> Symbol {
+        if let Some(symbol) = self.get_by_hash(hash, s) {
+            return symbol;
+        }
+
+        let id = self.strs.len() as u32;
+        self.strs.push(s.into());
+        match self.ids.entry(hash) {
+            Entry::Vacant(ve) => {
+                ve.insert(id);
+            }
+            Entry::Occupied(_) => self.collisions.push((hash, id)),
+        }
+        Symbol { hash, id }
+    }
+
+    #[inline]
+    fn get_by_hash(&self, hash: u64, s: &str) -> Option<Symbol> {
+        let colliding_ids = self
+            .collisions
+            .iter()
+            .filter(move |(collision_hash, _)| *collision_hash == hash)
+            .map(|&(_, id)| id);
+        self.ids
+            .get(&hash)
+            .copied()
+            .into_iter()
+            .chain(colliding_ids)
+            .find(|&id| self.strs.get(id as usize).is_some_and(|v| v.as_ref() == s))
+            .map(|id| Symbol { hash, id })
+    }
+
+    /// returns symbol of the string if it was interned.
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        self.get_by_hash(fxhash::hash_bytes(s.as_bytes()), s)
+    }
+
+    /// symbols must come from this table; unknown ids resolve into an empty string.
+    #[inline]
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        self.strs.get(symbol.id as usize).map_or("", |v| v.as_ref())
+    }
+
+    /// number of interned strings; empty string of the default symbol is not counted.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.strs.len() - 1
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut symbols = SymbolTable::default();
+
+        let a = symbols.intern("m_iHealth");
+        let b = symbols.intern("m_lifeState");
+        assert_eq!(symbols.intern("m_iHealth"), a);
+        assert_ne!(a, b);
+        assert_eq!(a.hash, fxhash::hash_bytes(b"m_iHealth"));
+        assert_eq!(symbols.resolve(a), "m_iHealth");
+        assert_eq!(symbols.resolve(b), "m_lifeState");
+        assert_eq!(symbols.get("m_lifeState"), Some(b));
+        assert_eq!(symbols.get("m_iMaxHealth"), None);
+        assert_eq!(symbols.resolve(Symbol::default()), "");
+        assert_eq!(symbols.len(), 2);
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let mut symbols = SymbolTable::default();
+        assert!(symbols.is_empty());
+        assert_eq!(symbols.intern(""), Symbol::default());
+        assert!(symbols.is_empty());
+        symbols.intern("m_iHealth");
+        assert!(!symbols.is_empty());
+    }
+
+    #[test]
+    fn test_intern_hash_collision() {
+        let mut symbols = SymbolTable::default();
+
+        let a = symbols.intern_with_hash(42, "a");
+        let b = symbols.intern_with_hash(42, "b");
+        let c = symbols.intern_with_hash(42, "c");
+        assert_ne!(a, b);
+        assert_ne!(b, c);
+        // colliding strings are deduplicated too
+        assert_eq!(symbols.intern_with_hash(42, "b"), b);
+        assert_eq!(symbols.intern_with_hash(42, "c"), c);
+        assert_eq!(symbols.intern_with_hash(42, "a"), a);
+        assert_eq!(symbols.resolve(b), "b");
+        assert_eq!(symbols.get_by_hash(42, "c"), Some(c));
+        assert_eq!(symbols.get_by_hash(42, "d"), None);
+        assert_eq!(symbols.len(), 3);
+    }
+}
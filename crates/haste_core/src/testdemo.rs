@@ -0,0 +1,556 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use valveprotos::common::{
+    CDemoClassInfo, CDemoFileInfo, CDemoFullPacket, CDemoSendTables, CDemoStringTables,
+    CsvcMsgCreateStringTable, CsvcMsgFlattenedSerializer, CsvcMsgPacketEntities, CsvcMsgServerInfo,
+    CsvcMsgUpdateStringTable, EDemoCommands, ProtoFlattenedSerializerFieldT,
+    ProtoFlattenedSerializerT, SvcMessages, c_demo_class_info, c_demo_string_tables,
+};
+use valveprotos::prost::Message;
+
+use crate::bitwriter::BitWriter;
+use crate::democlip::PacketBuilder;
+use crate::demowriter::DemoWriter;
+use crate::entities::DeltaHeader;
+use crate::fieldpath::write_field_paths;
+use crate::fieldvalue::FieldValue;
+use crate::instancebaseline::INSTANCE_BASELINE_TABLE_NAME;
+use crate::parser::Context;
+
+// NOTE: there's no way to produce a real replay in tests (and real replays are way too large to be
+// checked in), this module produces tiny synthetic ones that go through the same code paths.
+//
+// schema is fixed:
+// - class 0 `CTestEntity`:
+//   - `m_iHealth` (int32)
+//   - `m_bAlive` (bool)
+//   - `m_vecValues` (CUtlVector< uint32 >)
+//   - `CBodyComponent` (pointer to `CTestBody` that has `m_nCellX` and `m_nCellY`, both uint16)
+//   - `m_nScores` (uint32[3])
+// - class 1 `CTestGameRules`:
+//   - `m_nRound` (uint32)
+//
+// fields are addressed by field paths (see [`crate::fieldpath::FieldPath::iter`]), consts below
+// are there for convenience.
+
+pub const TEST_ENTITY_CLASS_ID: i32 = 0;
+pub const TEST_GAME_RULES_CLASS_ID: i32 = 1;
+
+pub const HEALTH: &[u8] = &[0];
+pub const ALIVE: &[u8] = &[1];
+/// elements are at `[2, i]`.
+pub const VALUES: &[u8] = &[2];
+pub const BODY: &[u8] = &[3];
+pub const BODY_CELL_X: &[u8] = &[3, 0];
+pub const BODY_CELL_Y: &[u8] = &[3, 1];
+/// fixed array, there's no length; elements are at `[4, i]`.
+pub const SCORES: &[u8] = &[4];
+
+pub const ROUND: &[u8] = &[0];
+
+/// tick interval of test replays; full packets are expected every 1800 ticks (see
+/// [`crate::parser::Context::tick_interval`]).
+pub const TICK_INTERVAL: f32 = 1.0 / 30.0;
+
+type Fields = BTreeMap<Vec<u8>, FieldValue>;
+
+// NOTE: same as what Entity::parse does with incoming values (see prune_stale_fields).
+fn apply_fields(state: &mut Fields, fields: &[(Vec<u8>, FieldValue)]) {
+    for (path, value) in fields {
+        if path.as_slice() == VALUES {
+            if let FieldValue::U64(len) = value {
+                state.retain(|key, _| {
+                    !(key.len() == 2 && key[0] == VALUES[0] && key[1] as u64 >= *len)
+                });
+            }
+        } else if path.as_slice() == BODY && matches!(value, FieldValue::Bool(false)) {
+            state.retain(|key, _| !(key.len() > 1 && key[0] == BODY[0]));
+        }
+        state.insert(path.clone(), value.clone());
+    }
+}
+
+fn to_owned_fields(fields: &[(&[u8], FieldValue)]) -> Vec<(Vec<u8>, FieldValue)> {
+    fields
+        .iter()
+        .map(|(path, value)| (path.to_vec(), value.clone()))
+        .collect()
+}
+
+/// only integers and bools are supported, that's what the schema consists of.
+fn write_fields(bw: &mut BitWriter, fields: &[(Vec<u8>, FieldValue)]) {
+    let paths: Vec<&[u8]> = fields.iter().map(|(path, _)| path.as_slice()).collect();
+    write_field_paths(bw, &paths);
+    for (_, value) in fields {
+        match value {
+            FieldValue::I64(value) => bw.write_varint64(*value),
+            FieldValue::U64(value) => bw.write_uvarint64(*value),
+            FieldValue::Bool(value) => bw.write_bool(*value),
+            _ => unreachable!("unsupported field value {value:?}"),
+        }
+    }
+}
+
+/// string table entry as it's encoded in SvcCreateStringTable and SvcUpdateStringTable (see
+/// [`crate::stringtables::StringTable::parse_update`]).
+fn write_string_table_entry(
+    bw: &mut BitWriter,
+    prev_index: i32,
+    index: i32,
+    string: Option<&str>,
+    user_data: &[u8],
+) {
+    bw.write_bool(index == prev_index + 1);
+    if index != prev_index + 1 {
+        bw.write_uvarint32((index - 1) as u32);
+    }
+    bw.write_bool(string.is_some());
+    if let Some(string) = string {
+        // NOTE: history is not used
+        bw.write_bool(false);
+        bw.write_bytes(string.as_bytes());
+        bw.write_byte(0);
+    }
+    bw.write_bool(true);
+    bw.write_ubitvar(user_data.len() as u32);
+    bw.write_bytes(user_data);
+}
+
+fn send_tables() -> CDemoSendTables {
+    let mut symbols: Vec<String> = Vec::new();
+    let mut sym = |s: &str| -> i32 {
+        match symbols.iter().position(|symbol| symbol == s) {
+            Some(i) => i as i32,
+            None => {
+                symbols.push(s.to_string());
+                symbols.len() as i32 - 1
+            }
+        }
+    };
+
+    let mut field = |var_name: &str, var_type: &str, field_serializer_name: Option<&str>| {
+        ProtoFlattenedSerializerFieldT {
+            var_type_sym: Some(sym(var_type)),
+            var_name_sym: Some(sym(var_name)),
+            field_serializer_name_sym: field_serializer_name.map(&mut sym),
+            ..Default::default()
+        }
+    };
+    let fields = vec![
+        field("m_nCellX", "uint16", None),
+        field("m_nCellY", "uint16", None),
+        field("m_iHealth", "int32", None),
+        field("m_bAlive", "bool", None),
+        field("m_vecValues", "CUtlVector< uint32 >", None),
+        field("CBodyComponent", "CBodyComponent", Some("CTestBody")),
+        field("m_nRound", "uint32", None),
+        field("m_nScores", "uint32[3]", None),
+    ];
+
+    // NOTE: serializers that are referenced by fields must come first.
+    let mut serializer = |name: &str, fields_index: Vec<i32>| ProtoFlattenedSerializerT {
+        serializer_name_sym: Some(sym(name)),
+        serializer_version: Some(0),
+        fields_index,
+    };
+    let serializers = vec![
+        serializer("CTestBody", vec![0, 1]),
+        serializer("CTestEntity", vec![2, 3, 4, 5, 7]),
+        serializer("CTestGameRules", vec![6]),
+    ];
+
+    let msg = CsvcMsgFlattenedSerializer {
+        serializers,
+        symbols,
+        fields,
+    }
+    .encode_to_vec();
+
+    let mut data = Vec::with_capacity(msg.len() + 10);
+    let mut size = msg.len() as u64;
+    while size >= 0x80 {
+        data.push((size as u8) | 0x80);
+        size >>= 7;
+    }
+    data.push(size as u8);
+    data.extend_from_slice(&msg);
+
+    CDemoSendTables { data: Some(data) }
+}
+
+fn class_info() -> CDemoClassInfo {
+    let class = |class_id: i32, network_name: &str| c_demo_class_info::ClassT {
+        class_id: Some(class_id),
+        network_name: Some(network_name.to_string()),
+        table_name: None,
+    };
+    CDemoClassInfo {
+        classes: vec![
+            class(TEST_ENTITY_CLASS_ID, "CTestEntity"),
+            class(TEST_GAME_RULES_CLASS_ID, "CTestGameRules"),
+        ],
+    }
+}
+
+struct TestEntity {
+    class_id: i32,
+    serial: u32,
+    fields: Fields,
+}
+
+enum EntityOp {
+    Create {
+        class_id: i32,
+        serial: u32,
+        fields: Vec<(Vec<u8>, FieldValue)>,
+    },
+    Update(Vec<(Vec<u8>, FieldValue)>),
+    Delete,
+}
+
+fn packet_entities_msg(is_delta: bool, mut entity_ops: Vec<(i32, EntityOp)>) -> Vec<u8> {
+    // NOTE: indices are delta encoded, they must be ascending.
+    entity_ops.sort_by_key(|(index, _)| *index);
+
+    let mut bw = BitWriter::new();
+    let mut prev_index = -1;
+    for (index, entity_op) in entity_ops.iter() {
+        debug_assert!(*index > prev_index, "multiple ops on entity #{index}");
+        bw.write_ubitvar((index - prev_index - 1) as u32);
+        prev_index = *index;
+
+        match entity_op {
+            EntityOp::Create {
+                class_id,
+                serial,
+                fields,
+            } => {
+                DeltaHeader::CREATE.write(&mut bw);
+                // NOTE: there are 2 classes, thus class id takes 1 bit.
+                bw.write_ubit64(*class_id as u64, 1);
+                bw.write_ubit64(*serial as u64, 17);
+                bw.write_uvarint32(0);
+                write_fields(&mut bw, fields);
+            }
+            EntityOp::Update(fields) => {
+                DeltaHeader::UPDATE.write(&mut bw);
+                write_fields(&mut bw, fields);
+            }
+            EntityOp::Delete => DeltaHeader::DELETE.write(&mut bw),
+        }
+    }
+
+    CsvcMsgPacketEntities {
+        updated_entries: Some(entity_ops.len() as i32),
+        is_delta: Some(is_delta),
+        entity_data: Some(bw.into_inner()),
+        ..Default::default()
+    }
+    .encode_to_vec()
+}
+
+/// writes synthetic replays (see schema above) that can be parsed with
+/// [`crate::parser::Parser`] and [`crate::demofile::DemoFile`].
+///
+/// entity changes and instancebaseline updates are being collected and written out as a single
+/// packet by [`TestDemoBuilder::write_packet`] (or [`TestDemoBuilder::write_full_packet`]).
+/// builder keeps track of entity state (in the same way as the parser does), thus full packets
+/// contain an actual snapshot.
+pub struct TestDemoBuilder {
+    demo_writer: DemoWriter<Cursor<Vec<u8>>>,
+    baselines: Vec<Fields>,
+    entities: BTreeMap<i32, TestEntity>,
+    string_table_msgs: Vec<(u32, Vec<u8>)>,
+    entity_ops: Vec<(i32, EntityOp)>,
+}
+
+impl TestDemoBuilder {
+    /// writes signon (server info, instancebaseline string table, send tables and class info)
+    /// followed by [`EDemoCommands::DemSyncTick`]. baselines of all classes are empty.
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut builder = Self {
+            demo_writer: DemoWriter::start_writing(Cursor::new(Vec::new()))?,
+            baselines: vec![Fields::new(), Fields::new()],
+            entities: BTreeMap::new(),
+            string_table_msgs: Vec::new(),
+            entity_ops: Vec::new(),
+        };
+
+        let mut bw = BitWriter::new();
+        for class_id in 0..builder.baselines.len() as i32 {
+            let class_id_str = class_id.to_string();
+            let user_data = builder.encode_baseline(class_id);
+            write_string_table_entry(
+                &mut bw,
+                class_id - 1,
+                class_id,
+                Some(&class_id_str),
+                &user_data,
+            );
+        }
+        let create_string_table = CsvcMsgCreateStringTable {
+            name: Some(INSTANCE_BASELINE_TABLE_NAME.to_string()),
+            num_entries: Some(builder.baselines.len() as i32),
+            user_data_fixed_size: Some(false),
+            flags: Some(0),
+            string_data: Some(bw.into_inner()),
+            data_compressed: Some(false),
+            using_varint_bitcounts: Some(true),
+            ..Default::default()
+        };
+        let server_info = CsvcMsgServerInfo {
+            tick_interval: Some(TICK_INTERVAL),
+            ..Default::default()
+        };
+
+        let mut packet_builder = PacketBuilder::new();
+        packet_builder.push_msg(
+            SvcMessages::SvcServerInfo as u32,
+            &server_info.encode_to_vec(),
+        );
+        packet_builder.push_msg(
+            SvcMessages::SvcCreateStringTable as u32,
+            &create_string_table.encode_to_vec(),
+        );
+
+        let demo_writer = &mut builder.demo_writer;
+        demo_writer.write_cmd(
+            EDemoCommands::DemSignonPacket,
+            -1,
+            &packet_builder.finish().encode_to_vec(),
+            false,
+        )?;
+        demo_writer.write_cmd(
+            EDemoCommands::DemSendTables,
+            -1,
+            &send_tables().encode_to_vec(),
+            false,
+        )?;
+        demo_writer.write_cmd(
+            EDemoCommands::DemClassInfo,
+            -1,
+            &class_info().encode_to_vec(),
+            false,
+        )?;
+        demo_writer.write_cmd(EDemoCommands::DemSyncTick, -1, &[], false)?;
+
+        Ok(builder)
+    }
+
+    fn encode_baseline(&self, class_id: i32) -> Vec<u8> {
+        let fields: Vec<(Vec<u8>, FieldValue)> = self.baselines[class_id as usize]
+            .iter()
+            .map(|(path, value)| (path.clone(), value.clone()))
+            .collect();
+        let mut bw = BitWriter::new();
+        write_fields(&mut bw, &fields);
+        bw.into_inner()
+    }
+
+    /// replaces baseline of the class. entities that are created afterwards start off with it.
+    pub fn update_baseline(&mut self, class_id: i32, fields: &[(&[u8], FieldValue)]) -> &mut Self {
+        let mut baseline = Fields::new();
+        apply_fields(&mut baseline, &to_owned_fields(fields));
+        self.baselines[class_id as usize] = baseline;
+
+        let mut bw = BitWriter::new();
+        write_string_table_entry(&mut bw, -1, class_id, None, &self.encode_baseline(class_id));
+        let msg = CsvcMsgUpdateStringTable {
+            // NOTE: instancebaseline is the only table.
+            table_id: Some(0),
+            num_changed_entries: Some(1),
+            string_data: Some(bw.into_inner()),
+        };
+        self.string_table_msgs.push((
+            SvcMessages::SvcUpdateStringTable as u32,
+            msg.encode_to_vec(),
+        ));
+        self
+    }
+
+    pub fn create_entity(
+        &mut self,
+        index: i32,
+        class_id: i32,
+        serial: u32,
+        fields: &[(&[u8], FieldValue)],
+    ) -> &mut Self {
+        let fields = to_owned_fields(fields);
+        let mut state = self.baselines[class_id as usize].clone();
+        apply_fields(&mut state, &fields);
+        self.entities.insert(
+            index,
+            TestEntity {
+                class_id,
+                serial,
+                fields: state,
+            },
+        );
+        self.entity_ops.push((
+            index,
+            EntityOp::Create {
+                class_id,
+                serial,
+                fields,
+            },
+        ));
+        self
+    }
+
+    pub fn update_entity(&mut self, index: i32, fields: &[(&[u8], FieldValue)]) -> &mut Self {
+        let fields = to_owned_fields(fields);
+        if let Some(entity) = self.entities.get_mut(&index) {
+            apply_fields(&mut entity.fields, &fields);
+        }
+        self.entity_ops.push((index, EntityOp::Update(fields)));
+        self
+    }
+
+    pub fn delete_entity(&mut self, index: i32) -> &mut Self {
+        self.entities.remove(&index);
+        self.entity_ops.push((index, EntityOp::Delete));
+        self
+    }
+
+    /// writes collected baseline updates and entity changes as [`EDemoCommands::DemPacket`].
+    pub fn write_packet(&mut self, tick: i32) -> Result<&mut Self, anyhow::Error> {
+        let mut packet_builder = PacketBuilder::new();
+        for (msg_type, data) in self.string_table_msgs.drain(..) {
+            packet_builder.push_msg(msg_type, &data);
+        }
+        if !self.entity_ops.is_empty() {
+            let entity_ops = std::mem::take(&mut self.entity_ops);
+            let msg = packet_entities_msg(true, entity_ops);
+            packet_builder.push_msg(SvcMessages::SvcPacketEntities as u32, &msg);
+        }
+        self.demo_writer.write_cmd(
+            EDemoCommands::DemPacket,
+            tick,
+            &packet_builder.finish().encode_to_vec(),
+            false,
+        )?;
+        Ok(self)
+    }
+
+    /// writes [`EDemoCommands::DemFullPacket`] that contains snapshot of instancebaseline string
+    /// table and a packet with collected baseline updates followed by creation of all entities.
+    ///
+    /// NOTE: collected entity changes are already reflected in the snapshot, they are not being
+    /// written out separately; [`crate::parser::Parser::run_to_end`] ignores full packets, thus
+    /// call [`TestDemoBuilder::write_packet`] first if that matters.
+    pub fn write_full_packet(&mut self, tick: i32) -> Result<&mut Self, anyhow::Error> {
+        let string_table = CDemoStringTables {
+            tables: vec![c_demo_string_tables::TableT {
+                table_name: Some(INSTANCE_BASELINE_TABLE_NAME.to_string()),
+                items: (0..self.baselines.len() as i32)
+                    .map(|class_id| c_demo_string_tables::ItemsT {
+                        str: Some(class_id.to_string()),
+                        data: Some(self.encode_baseline(class_id)),
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+        };
+
+        let mut packet_builder = PacketBuilder::new();
+        for (msg_type, data) in self.string_table_msgs.drain(..) {
+            packet_builder.push_msg(msg_type, &data);
+        }
+        self.entity_ops.clear();
+        let entity_ops = self
+            .entities
+            .iter()
+            .map(|(index, entity)| {
+                let entity_op = EntityOp::Create {
+                    class_id: entity.class_id,
+                    serial: entity.serial,
+                    fields: entity
+                        .fields
+                        .iter()
+                        .map(|(path, value)| (path.clone(), value.clone()))
+                        .collect(),
+                };
+                (*index, entity_op)
+            })
+            .collect();
+        let msg = packet_entities_msg(false, entity_ops);
+        packet_builder.push_msg(SvcMessages::SvcPacketEntities as u32, &msg);
+
+        let full_packet = CDemoFullPacket {
+            string_table: Some(string_table),
+            packet: Some(packet_builder.finish()),
+        };
+        self.demo_writer.write_cmd(
+            EDemoCommands::DemFullPacket,
+            tick,
+            &full_packet.encode_to_vec(),
+            false,
+        )?;
+        Ok(self)
+    }
+
+    /// writes [`EDemoCommands::DemStop`] and file info.
+    pub fn finish(mut self, tick: i32) -> Result<Vec<u8>, anyhow::Error> {
+        self.demo_writer
+            .write_cmd(EDemoCommands::DemStop, tick, &[], false)?;
+        let file_info = CDemoFileInfo {
+            playback_time: Some(tick as f32 * TICK_INTERVAL),
+            playback_ticks: Some(tick),
+            playback_frames: Some(tick),
+            ..Default::default()
+        };
+        self.demo_writer.write_file_info(tick, &file_info)?;
+        Ok(self.demo_writer.finish()?.into_inner())
+    }
+}
+
+/// entities (along with their fields) formatted and sorted, so that states of different parsers
+/// can be compared.
+pub fn entities_state(ctx: &Context) -> Vec<String> {
+    let mut entities: Vec<String> = ctx
+        .entities()
+        .into_iter()
+        .flat_map(|entities| entities.iter())
+        .map(|(index, entity)| {
+            let mut fields: Vec<String> = entity
+                .iter()
+                .map(|(key, value)| format!("{key}={value:?}"))
+                .collect();
+            fields.sort();
+            format!(
+                "#{index} class {} serial {}: {fields:?}",
+                entity.class_id(),
+                entity.serial()
+            )
+        })
+        .collect();
+    entities.sort();
+    entities
+}
+
+/// same as [`entities_state`], but for items of string tables.
+pub fn string_tables_state(ctx: &Context) -> Vec<String> {
+    ctx.string_tables()
+        .into_iter()
+        .flat_map(|string_tables| string_tables.tables())
+        .flat_map(|string_table| {
+            let mut items: Vec<String> = string_table
+                .items()
+                .map(|(index, item)| {
+                    let user_data = item
+                        .user_data
+                        .as_ref()
+                        .map(|user_data| unsafe { &*user_data.get() });
+                    format!(
+                        "{} #{index} {:?}: {user_data:?}",
+                        string_table.name(),
+                        item.string
+                    )
+                })
+                .collect();
+            items.sort();
+            items
+        })
+        .collect()
+}
@@ -41,13 +41,13 @@ impl Visitor for MyVisitor {
             LIFE_ALIVE => eprintln!(
                 "{:>6}: {} at index {} has spawned",
                 ctx.tick(),
-                entity.serializer().serializer_name.str,
+                ctx.symbols().resolve(entity.serializer().serializer_name),
                 entity.index(),
             ),
             LIFE_DEAD => eprintln!(
                 "{:>6}: {} at index {} has died",
                 ctx.tick(),
-                entity.serializer().serializer_name.str,
+                ctx.symbols().resolve(entity.serializer().serializer_name),
                 entity.index(),
             ),
             _ => {}
@@ -4,7 +4,7 @@ use std::time::Instant;
 
 use anyhow::{Context, Result};
 use haste::demofile::DemoFile;
-use haste::demostream::DemoStream;
+use haste::demostream::SeekableDemoStream;
 use haste::parser::Parser;
 use rand::Rng;
 
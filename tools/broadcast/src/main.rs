@@ -1,11 +1,16 @@
 use std::fs::File;
-use std::io::{BufReader, Write};
+use std::io::{BufReader, BufWriter, Write};
+use std::net::TcpListener;
 use std::time::Duration;
 
 use anyhow::{bail, Result};
-use haste::broadcast::{BroadcastFile, BroadcastHttp};
+use haste::broadcast::{
+    BroadcastConverter, BroadcastFile, BroadcastHttp, BroadcastServer, BroadcastServerOptions,
+};
+use haste::demofile::DemoFile;
 use haste::demostream::CmdHeader;
 use haste::parser::{Context, Parser, Visitor};
+use haste::valveprotos::common::CDemoFileHeader;
 
 struct MyVisitor;
 
@@ -26,6 +31,9 @@ struct DownloadCommand {
     /// write broadcast to the given file;
     #[argh(option)]
     output: String,
+    /// start from the very first fragment instead of the live one
+    #[argh(switch)]
+    from_beginning: bool,
 }
 
 impl DownloadCommand {
@@ -33,7 +41,11 @@ impl DownloadCommand {
         let http_client = reqwest::Client::builder()
             .timeout(Duration::from_secs(3))
             .build()?;
-        let mut broadcast = BroadcastHttp::start_streaming(http_client, &self.url).await?;
+        let mut broadcast = if self.from_beginning {
+            BroadcastHttp::start_streaming_from_beginning(http_client, &self.url).await?
+        } else {
+            BroadcastHttp::start_streaming(http_client, &self.url).await?
+        };
         let mut file = File::create(&self.output)?;
         while let Some(packet) = broadcast.next_packet().await {
             file.write_all(packet?.as_ref())?;
@@ -100,11 +112,127 @@ impl ParseCommand {
     }
 }
 
+/// convert broadcast into a regular replay
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "convert")]
+struct ConvertCommand {
+    /// broadcast url
+    #[argh(option)]
+    url: Option<String>,
+    /// read broadcast from the given file
+    #[argh(option)]
+    filepath: Option<String>,
+    /// write replay to the given file
+    #[argh(option)]
+    output: String,
+    /// start from the very first fragment instead of the live one
+    #[argh(switch)]
+    from_beginning: bool,
+}
+
+impl ConvertCommand {
+    async fn convert_from_url(url: &str, output: &str, from_beginning: bool) -> Result<()> {
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(3))
+            .build()?;
+
+        let mut demo_stream = if from_beginning {
+            BroadcastHttp::start_streaming_from_beginning(http_client, url).await?
+        } else {
+            BroadcastHttp::start_streaming(http_client, url).await?
+        };
+        let sync_response = demo_stream.sync_response();
+        let file_header = CDemoFileHeader {
+            map_name: Some(sync_response.map.clone()),
+            network_protocol: Some(sync_response.protocol),
+            ..Default::default()
+        };
+
+        let file = File::create(output)?;
+        let mut converter =
+            BroadcastConverter::start_writing_with_file_header(BufWriter::new(file), &file_header)?;
+
+        while let Some(packet) = demo_stream.next_packet().await {
+            packet?;
+            converter.write_stream(&mut demo_stream)?;
+        }
+
+        converter.finish()?;
+        Ok(())
+    }
+
+    fn convert_from_filepath(filepath: &str, output: &str) -> Result<()> {
+        let file = File::open(filepath)?;
+        let buf_reader = BufReader::new(file);
+        let mut broadcast_file = BroadcastFile::start_reading(buf_reader);
+
+        let file = File::create(output)?;
+        let mut converter = BroadcastConverter::start_writing(BufWriter::new(file))?;
+        converter.write_stream(&mut broadcast_file)?;
+        converter.finish()?;
+        Ok(())
+    }
+
+    async fn execute(self) -> Result<()> {
+        if let (Some(url), None) = (&self.url, &self.filepath) {
+            return Self::convert_from_url(url, &self.output, self.from_beginning).await;
+        }
+
+        if let (None, Some(filepath)) = (&self.url, &self.filepath) {
+            return Self::convert_from_filepath(filepath, &self.output);
+        }
+
+        bail!("invalid args; run {} help", env!("CARGO_PKG_NAME"));
+    }
+}
+
+/// serve replay as a broadcast
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "serve")]
+struct ServeCommand {
+    /// read replay from the given file
+    #[argh(option)]
+    filepath: String,
+    /// address to listen on
+    #[argh(option, default = "String::from(\"127.0.0.1:8080\")")]
+    addr: String,
+    /// playback speed; 1 is real-time
+    #[argh(option, default = "1.0")]
+    speed: f64,
+    /// the interval between full keyframes, in seconds
+    #[argh(option, default = "3")]
+    keyframe_interval: u32,
+}
+
+impl ServeCommand {
+    fn execute(self) -> Result<()> {
+        let file = File::open(&self.filepath)?;
+        let buf_reader = BufReader::new(file);
+        let demo_file = DemoFile::start_reading(buf_reader)?;
+        let options = BroadcastServerOptions {
+            keyframe_interval: self.keyframe_interval,
+            speed: self.speed,
+        };
+        let mut server = BroadcastServer::new(demo_file, options)?;
+
+        let listener = TcpListener::bind(&self.addr)?;
+        eprintln!(
+            "serving {} fragments on http://{}",
+            server.num_fragments(),
+            listener.local_addr()?
+        );
+        server.serve(&listener)?;
+        Ok(())
+    }
+}
+
 #[derive(argh::FromArgs)]
 #[argh(subcommand)]
 enum SubCommands {
     Download(DownloadCommand),
     Parse(ParseCommand),
+    Convert(ConvertCommand),
+    Serve(ServeCommand),
 }
 
 impl SubCommands {
@@ -112,6 +240,9 @@ impl SubCommands {
         match self {
             SubCommands::Download(download) => download.execute().await,
             SubCommands::Parse(parse) => parse.execute().await,
+            SubCommands::Convert(convert) => convert.execute().await,
+            // NOTE: server is blocking, it does not need the async runtime.
+            SubCommands::Serve(serve) => serve.execute(),
         }
     }
 }
@@ -0,0 +1,37 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use anyhow::Result;
+use haste::democlip;
+use haste::demofile::DemoFile;
+
+/// clip replay to a tick range; the result is a standalone replay
+#[derive(argh::FromArgs)]
+struct Args {
+    /// read replay from the given file
+    #[argh(positional)]
+    input: String,
+    /// write clipped replay to the given file
+    #[argh(positional)]
+    output: String,
+    /// first tick of the clip
+    #[argh(option)]
+    start: i32,
+    /// last tick of the clip
+    #[argh(option)]
+    end: i32,
+}
+
+fn main() -> Result<()> {
+    let args: Args = argh::from_env();
+
+    let file = File::open(&args.input)?;
+    let buf_reader = BufReader::new(file);
+    let mut demo_file = DemoFile::start_reading(buf_reader)?;
+
+    let file = File::create(&args.output)?;
+    let buf_writer = BufWriter::new(file);
+    democlip::clip(&mut demo_file, buf_writer, args.start, args.end)?;
+
+    Ok(())
+}